@@ -128,7 +128,7 @@ pub async fn estimate_gas(chain_id: u64, params: EstimateGasParams) -> Result<Op
     }
 }
 
-fn get_rpc_provider(chain_id: u64) -> Result<Option<String>> {
+pub(super) fn get_rpc_provider(chain_id: u64) -> Result<Option<String>> {
     read_state(|state| {
         let chain_state = state
             .chains
@@ -0,0 +1,113 @@
+use core::str;
+use std::str::FromStr;
+
+use candid::Nat;
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod,
+};
+use num_traits::ToPrimitive;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::model::{
+    errors::{BlockchainError, Result, SystemError},
+    memory::heap::read_state,
+};
+
+use super::estimate_gas::get_rpc_provider;
+
+#[derive(Serialize, Debug)]
+struct EthCallParams {
+    to: String,
+    data: String,
+}
+
+/// Executes a read-only `eth_call` against `to` on the primary RPC provider of `chain_id`.
+pub async fn eth_call(chain_id: u64, to: &str, data: Vec<u8>) -> Result<Vec<u8>> {
+    let proxy_url = read_state(|s| s.proxy_url.clone());
+
+    let rpc_provider_url = get_rpc_provider(chain_id)?
+        .ok_or(BlockchainError::RpcProviderNotFound)?
+        .replace("https://", "");
+    let (base_url, endpoint) = if let Some((domain, path)) = rpc_provider_url.split_once('/') {
+        (domain, path.to_string())
+    } else {
+        (rpc_provider_url.as_str(), "/".to_string())
+    };
+
+    let request_headers = vec![
+        HttpHeader {
+            name: "Content-Type".to_string(),
+            value: "application/json".to_string(),
+        },
+        HttpHeader {
+            name: "x-forwarded-host".to_string(),
+            value: base_url.to_string(),
+        },
+        HttpHeader {
+            name: "idempotency-key".to_string(),
+            value: format!("eth-call-{}-{}", chain_id, ic_cdk::api::time()),
+        },
+    ];
+
+    let params = EthCallParams {
+        to: to.to_string(),
+        data: format!("0x{}", hex::encode(data)),
+    };
+    let request_body = json!({
+        "jsonrpc": "2.0",
+        "method": "eth_call",
+        "params": [params, "latest"],
+        "id": 1
+    })
+    .to_string()
+    .into_bytes();
+
+    let request = CanisterHttpRequestArgument {
+        url: format!("{}/{}", proxy_url, endpoint),
+        method: HttpMethod::POST,
+        body: Some(request_body),
+        max_response_bytes: Some(2048),
+        transform: None,
+        headers: request_headers,
+    };
+
+    let cycles = 10_000_000_000;
+    match http_request(request, cycles).await {
+        Ok((response,)) => {
+            if response
+                .status
+                .ne(&Nat::from_str("200").unwrap_or_default())
+            {
+                return Err(SystemError::HttpRequestError(
+                    response.status.0.to_u64().unwrap_or_default(),
+                    "HTTP error".to_string(),
+                ))?;
+            }
+
+            let str_body = str::from_utf8(&response.body).map_err(|_| SystemError::Utf8Error)?;
+
+            let json_response: serde_json::Value = serde_json::from_str(str_body)
+                .map_err(|e| SystemError::ParseError(e.to_string()))?;
+            ic_cdk::println!("[eth_call] json_response = {}", json_response);
+
+            if let Some(error) = json_response.get("error") {
+                let error_message = error["message"]
+                    .as_str()
+                    .unwrap_or("Unknown error")
+                    .to_string();
+                let error_code = error["code"].as_i64().unwrap_or(0);
+                return Err(
+                    BlockchainError::EvmExecutionReverted(error_code, error_message).into(),
+                );
+            }
+
+            let result = json_response["result"]
+                .as_str()
+                .ok_or_else(|| SystemError::ParseError("eth_call result missing".to_string()))?;
+            hex::decode(result.trim_start_matches("0x"))
+                .map_err(|e| SystemError::ParseError(e.to_string()).into())
+        }
+        Err((r, m)) => Err(SystemError::HttpRequestError(r as u64, m).into()),
+    }
+}
@@ -1,4 +1,5 @@
 mod estimate_gas;
+mod eth_call;
 pub mod event;
 pub mod fees;
 pub mod helper;
@@ -8,3 +9,4 @@ pub mod transaction;
 pub mod vault;
 
 pub use estimate_gas::{estimate_gas, EstimateGasParams};
+pub use eth_call::eth_call;
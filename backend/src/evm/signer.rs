@@ -1,6 +1,7 @@
 use std::str::FromStr;
 
-use ethers_core::abi::ethereum_types::{Address, U256};
+use ethers_core::abi::ethereum_types::{Address, H256, U256};
+use ethers_core::abi::{encode, Token};
 use ethers_core::k256::ecdsa::{RecoveryId, Signature as K256Signature, VerifyingKey};
 use ethers_core::types::transaction::eip1559::Eip1559TransactionRequest;
 use ethers_core::types::{Bytes, Signature};
@@ -13,6 +14,8 @@ use crate::errors::{BlockchainError, Result, UserError};
 use crate::model::memory::heap::read_state;
 use crate::types::evm::request::SignRequest;
 
+const EIP1271_MAGIC_VALUE: [u8; 4] = [0x16, 0x26, 0xba, 0x7e];
+
 pub async fn sign_transaction(req: SignRequest) -> String {
     const EIP1559_TX_ID: u8 = 2;
 
@@ -118,15 +121,40 @@ pub fn pubkey_bytes_to_address(pubkey_bytes: &[u8]) -> String {
     ethers_core::utils::to_checksum(&Address::from_slice(&hash[12..32]), None)
 }
 
-pub fn verify_signature(evm_address: &str, message: &str, signature: &str) -> Result<()> {
-    let recovered_address = Signature::from_str(signature)
-        .map_err(|_| UserError::InvalidSignature)?
-        .recover(message)
-        .map_err(|_| UserError::InvalidSignature)?;
+/// Verifies `signature` over `digest` for `evm_address`.
+///
+/// EOA signatures are recovered locally. If `contract_wallet` is set, a signature that does
+/// not recover to the address is instead checked through EIP-1271 `isValidSignature` on
+/// `chain_id`. That costs an outcall, so it is only done when the client asks for it.
+pub async fn verify_signature(
+    chain_id: u64,
+    evm_address: &str,
+    digest: [u8; 32],
+    signature: &str,
+    contract_wallet: bool,
+) -> Result<()> {
+    let address = Address::from_str(evm_address).map_err(|_| BlockchainError::InvalidAddress)?;
+
+    if let Ok(signature) = Signature::from_str(signature) {
+        if matches!(signature.recover(H256::from(digest)), Ok(recovered) if recovered == address) {
+            return Ok(());
+        }
+    }
+    if !contract_wallet {
+        return Err(UserError::InvalidSignature)?;
+    }
 
-    if recovered_address
-        == Address::from_str(evm_address).map_err(|_| BlockchainError::InvalidAddress)?
-    {
+    let signature_bytes =
+        hex::decode(signature.trim_start_matches("0x")).map_err(|_| UserError::InvalidSignature)?;
+    // the isValidSignature(bytes32,bytes) selector is the magic value itself
+    let mut data = EIP1271_MAGIC_VALUE.to_vec();
+    data.extend(encode(&[
+        Token::FixedBytes(digest.to_vec()),
+        Token::Bytes(signature_bytes),
+    ]));
+
+    let result = super::eth_call(chain_id, evm_address, data).await?;
+    if result.get(..4) == Some(&EIP1271_MAGIC_VALUE[..]) {
         Ok(())
     } else {
         Err(UserError::InvalidSignature)?
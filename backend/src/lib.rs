@@ -10,27 +10,36 @@ use std::collections::{HashMap, HashSet};
 use candid::Principal;
 use evm_rpc_canister_types::BlockTag;
 use ic_cdk::api::management_canister::http_request::{HttpResponse, TransformArgs};
-use icrc_ledger_types::icrc1::{account::Account, transfer::NumTokens};
 
 use evm::{fees, transaction, vault::Ic2P2ramp};
 use icp::vault::Ic2P2ramp as ICPRamp;
 use management::{
-    order as order_management, payment as payment_management, random, user as user_management,
+    moderation as moderation_management, order as order_management, payment as payment_management,
+    rates::setup_rate_prefetch_timer, reputation as reputation_management,
+    treasury as treasury_management, user as user_management,
 };
 use model::errors::{self, BlockchainError, OrderError, Result, SystemError, UserError};
 use model::types::{
     self,
+    access::{AccessRole, RoleAssignment},
     evm::{
         gas::{self, ChainGasTracking},
         logs::{EvmTransactionLog, TransactionStatus},
         nonce,
+        siwe::SiweMessage,
         token::{self, Token, TokenManager},
         transaction::{TransactionAction, TransactionVariant},
     },
-    exchange_rate::{ExchangeRateCache, CACHE_DURATION},
+    exchange_rate::{ExchangeRateCache, RatePrefetchStatus, RejectedRate, CACHE_DURATION},
     icp::{get_icp_token, IcpToken},
-    orders::{EvmOrderInput, OrderFilter, OrderState},
-    session::Session,
+    limits::VerificationLevel,
+    moderation::{Appeal, AuditEntry},
+    orders::{EvmOrderInput, OrderFilter, OrderQuote, OrderState},
+    rate_limit::{RateLimitClass, RateLimitEntry, RateLimitKey},
+    reputation::UserReputation,
+    secrets::{SecretInfo, SecretKey},
+    session::{Session, SessionInfo},
+    treasury::{TreasuryAction, TreasuryProposal},
     user::{User, UserType},
     AddressType, AuthenticationData, Blockchain, Crypto, LoginAddress, PaymentProvider,
     PaymentProviderType, TransactionAddress,
@@ -40,8 +49,8 @@ use model::{
     memory::{
         self,
         heap::{
-            self, initialize_state, logs, read_state, setup_timers, upgrade, InstallArg, State,
-            STATE,
+            self, initialize_state, logs, mutate_state, read_state, setup_timers, upgrade,
+            InstallArg, State, STATE,
         },
         stable::{self, orders, spent_transactions},
     },
@@ -68,11 +77,16 @@ fn post_upgrade(install_arg: InstallArg) {
         InstallArg::Reinstall(_) => ic_cdk::trap("InitArg not valid for reinstall"),
         InstallArg::Upgrade(update_arg) => {
             upgrade::post_upgrade(update_arg.clone());
+            stable::users::rebuild_login_index();
+            stable::orders::rebuild_trading_pair_index();
+            order_management::setup_legacy_payee_sealing();
             if let Some(update_arg) = update_arg {
                 if update_arg.ecdsa_key_id.is_some() {
                     setup_timers();
                 }
             }
+            setup_rate_prefetch_timer();
+            heap::setup_rate_limit_pruning_timer();
         }
     }
 
@@ -93,8 +107,12 @@ fn init(install_arg: InstallArg) {
     );
 
     match install_arg {
-        InstallArg::Reinstall(init_arg) => {
-            initialize_state(State::try_from(init_arg).expect("BUG: failed to initialize minter"))
+        InstallArg::Reinstall(mut init_arg) => {
+            let init_secrets = init_arg.take_secrets();
+            initialize_state(State::try_from(init_arg).expect("BUG: failed to initialize minter"));
+            for (key, value) in init_secrets {
+                stable::secrets::rotate_secret(key, value);
+            }
         }
         InstallArg::Upgrade(_) => ic_cdk::trap("UpdateArg not valid for reinstall"),
     }
@@ -108,6 +126,8 @@ fn init(install_arg: InstallArg) {
 
     ic_cdk::println!("[init] new state = {:?}", state);
     setup_timers();
+    setup_rate_prefetch_timer();
+    heap::setup_rate_limit_pruning_timer();
 }
 
 #[ic_cdk::query]
@@ -221,7 +241,7 @@ pub async fn create_evm_order_with_tx(
     amount: u128,
     token: Option<String>,
 ) -> Result<u64> {
-    guards::only_controller()?;
+    guards::only_role(AccessRole::Operator)?;
 
     let transaction_variant = match token {
         Some(_) => TransactionVariant::Token,
@@ -281,7 +301,7 @@ fn get_order_tx_log(
         let user = stable::users::get_user(&user_token.0)?;
         user.validate_session(&user_token.1)?;
     } else {
-        guards::only_controller()?;
+        guards::only_role(AccessRole::Auditor)?;
     }
     Ok(heap::logs::get_transaction_log(order_id))
 }
@@ -325,6 +345,7 @@ fn print_constants() -> String {
         User Session's Expiration Time = {}s\n\
         Lock Nonce Timeout Time = {}s\n\
         Exchange Rate Cache Duration = {}s\n\
+        Order Quote Validity = {}s\n\
         Offramper Fiat Fee = {}%\n\
         Onramper Crypto Fee = {}%\n\
         Evm Retry Attempts = {}\n\
@@ -334,6 +355,7 @@ fn print_constants() -> String {
         CACHE_DURATION,
         nonce::LOCK_NONCE_TIME_SECONDS,
         Session::EXPIRATION_SECS,
+        types::orders::QUOTE_DURATION_SECONDS,
         (100. / types::orders::fees::OFFRAMPER_FIAT_FEE_DENOM as f64),
         (100. / types::orders::fees::ADMIN_CRYPTO_FEE_DENOM as f64),
         transaction::MAX_RETRY_ATTEMPTS,
@@ -353,7 +375,7 @@ fn get_icp_token_info(ledger_principal: Principal) -> Result<IcpToken> {
 
 #[ic_cdk::update]
 async fn register_icp_tokens(icp_canisters: Vec<String>) -> Result<()> {
-    guards::only_controller()?;
+    guards::only_role(AccessRole::Admin)?;
     ICPRamp::register_icp_token(icp_canisters).await
 }
 
@@ -375,7 +397,7 @@ async fn get_evm_tokens(chain_id: u64) -> Result<Vec<Token>> {
 
 #[ic_cdk::update]
 async fn register_evm_tokens(chain_id: u64, tokens: Vec<(String, u8, String)>) -> Result<()> {
-    guards::only_controller()?;
+    guards::only_role(AccessRole::Admin)?;
 
     let mut new_tokens = TokenManager::new();
     for (token_address, decimals, rate_symbol) in tokens {
@@ -397,65 +419,153 @@ async fn register_evm_tokens(chain_id: u64, tokens: Vec<(String, u8, String)>) -
 
 #[ic_cdk::query]
 async fn view_canister_balances() -> Result<HashMap<String, f64>> {
-    guards::only_controller()?;
+    guards::only_role(AccessRole::Auditor)?;
     ICPRamp::get_canister_balances().await
 }
 
+/// Proposes an ICP ledger transfer, executed once approved by the treasury signers.
 #[ic_cdk::update]
-async fn transfer_canister_funds(
+fn transfer_canister_funds(
     ledger_canister: Principal,
     to_principal: Principal,
     amount: u128,
-) -> Result<()> {
-    guards::only_controller()?;
-
-    let fee = get_icp_token(&ledger_canister)?.fee;
-    let to_account = Account {
-        owner: to_principal,
-        subaccount: None,
-    };
-
-    ICPRamp::transfer(
+) -> Result<u64> {
+    guards::only_role(AccessRole::Treasury)?;
+    treasury_management::propose(TreasuryAction::CanisterTransfer {
         ledger_canister,
-        to_account,
-        NumTokens::from(amount) - fee.clone(),
-        Some(fee),
-    )
-    .await?;
-
-    Ok(())
+        to_principal,
+        amount,
+    })
 }
 
+/// Proposes a withdrawal of the collected vault fees to the canister address.
 #[ic_cdk::update]
-async fn withdraw_evm_fees(chain_id: u64, amount: u128, token: Option<String>) -> Result<()> {
-    guards::only_controller()?;
-
-    let canister_address =
-        read_state(|s| s.evm_address.clone()).expect("evm address should be initialized");
-    if let Some(token_address) = token.clone() {
-        token::evm_token_is_approved(chain_id, &token_address)?;
-    }
-
-    Ic2P2ramp::withdraw_deposit(chain_id, 0, canister_address, token, amount, 0).await?;
-
-    Ok(())
+fn withdraw_evm_fees(chain_id: u64, amount: u128, token: Option<String>) -> Result<u64> {
+    guards::only_role(AccessRole::Treasury)?;
+    treasury_management::propose(TreasuryAction::EvmFeeWithdrawal {
+        chain_id,
+        amount,
+        token,
+    })
 }
 
+/// Proposes a transfer from the canister EVM address.
 #[ic_cdk::update]
-async fn transfer_evm_funds(
+fn transfer_evm_funds(
     chain_id: u64,
     to: String,
     amount: u128,
     token: Option<String>,
     estimated_gas: Option<u64>,
-) -> Result<()> {
-    guards::only_controller()?;
-    helpers::validate_evm_address(&to)?;
+) -> Result<u64> {
+    guards::only_role(AccessRole::Treasury)?;
+    treasury_management::propose(TreasuryAction::EvmTransfer {
+        chain_id,
+        to,
+        amount,
+        token,
+        estimated_gas,
+    })
+}
 
-    if let Some(token) = token.clone() {
-        token::evm_token_is_approved(chain_id, &token)?;
+#[ic_cdk::update]
+fn approve_treasury_proposal(proposal_id: u64) -> Result<()> {
+    treasury_management::approve(proposal_id)
+}
+
+#[ic_cdk::update]
+fn cancel_treasury_proposal(proposal_id: u64) -> Result<()> {
+    treasury_management::cancel(proposal_id)
+}
+
+#[ic_cdk::update]
+async fn execute_treasury_proposal(proposal_id: u64) -> Result<()> {
+    guards::only_role(AccessRole::Treasury)?;
+    treasury_management::execute(proposal_id).await
+}
+
+#[ic_cdk::query]
+fn get_treasury_proposal(proposal_id: u64) -> Result<TreasuryProposal> {
+    guards::only_role(AccessRole::Auditor)?;
+    stable::treasury::get_proposal(proposal_id)
+}
+
+#[ic_cdk::query]
+fn get_treasury_proposals(
+    page: Option<u32>,
+    page_size: Option<u32>,
+) -> Result<Vec<TreasuryProposal>> {
+    guards::only_role(AccessRole::Auditor)?;
+    Ok(stable::treasury::get_proposals(page, page_size))
+}
+
+// ------
+// Access
+// ------
+
+#[ic_cdk::update]
+fn grant_role(principal: Principal, role: AccessRole) -> Result<()> {
+    guards::only_role(AccessRole::Admin)?;
+    heap::grant_role(principal, role)
+}
+
+#[ic_cdk::update]
+fn revoke_role(principal: Principal, role: AccessRole) -> Result<()> {
+    guards::only_role(AccessRole::Admin)?;
+    heap::revoke_role(&principal, role)
+}
+
+#[ic_cdk::query]
+fn get_role_assignments() -> Result<Vec<RoleAssignment>> {
+    guards::only_role(AccessRole::Auditor)?;
+    Ok(heap::get_role_assignments())
+}
+
+/// Roles held by the caller, including the ones implied by being a controller or admin.
+#[ic_cdk::query]
+fn get_caller_roles() -> Vec<AccessRole> {
+    let caller = ic_cdk::caller();
+    [
+        AccessRole::Admin,
+        AccessRole::Operator,
+        AccessRole::Arbitrator,
+        AccessRole::Auditor,
+        AccessRole::Treasury,
+    ]
+    .into_iter()
+    .filter(|role| heap::has_role(&caller, *role))
+    .collect()
+}
+
+/// Replaces a provider credential at runtime. The value is never returned by any endpoint.
+#[ic_cdk::update]
+fn rotate_secret(key: SecretKey, value: Vec<u8>) -> Result<SecretInfo> {
+    guards::only_role(AccessRole::Admin)?;
+    if key == SecretKey::PayeeMasterKey {
+        return Err(SystemError::InvalidInput(
+            "The payee master key cannot be rotated".to_string(),
+        )
+        .into());
+    }
+    if value.is_empty() {
+        return Err(SystemError::InvalidInput("Secret is empty".to_string()).into());
     }
-    Ic2P2ramp::transfer(chain_id, &to, amount, token, estimated_gas).await
+
+    let info = stable::secrets::rotate_secret(key, value);
+    if key == SecretKey::PaypalClientSecret {
+        // the cached token was issued for the previous secret
+        mutate_state(|s| {
+            s.paypal.access_token = None;
+            s.paypal.token_expiration = None;
+        });
+    }
+    Ok(info)
+}
+
+#[ic_cdk::query]
+fn get_secrets_info() -> Result<Vec<SecretInfo>> {
+    guards::only_role(AccessRole::Auditor)?;
+    Ok(stable::secrets::get_secrets_info())
 }
 
 // -----
@@ -469,6 +579,7 @@ async fn register_user(
     login_address: LoginAddress,
     password: Option<String>,
 ) -> Result<User> {
+    heap::check_rate_limit(RateLimitClass::Auth)?;
     user_management::register_user(user_type, payment_providers, login_address, password).await
 }
 
@@ -476,44 +587,179 @@ async fn register_user(
 async fn authenticate_user(
     login_address: LoginAddress,
     auth_data: Option<AuthenticationData>,
-) -> Result<User> {
+    bind_to_caller: Option<bool>,
+    session_name: Option<String>,
+) -> Result<(User, Session)> {
     login_address.validate()?;
+    heap::check_rate_limit(RateLimitClass::Auth)?;
     let user_id = stable::users::find_user_by_login_address(&login_address)?;
     let user = stable::users::get_user(&user_id)?;
-    user.verify_user_auth(auth_data)?;
+    // held in flight while verifying, so that a signature cannot be replayed while verification awaits
+    let auth_message = user_management::take_auth_message(user_id)?;
+    if let Err(e) = user.verify_user_auth(&login_address, auth_data).await {
+        user_management::restore_auth_message(user_id, auth_message)?;
+        return Err(e);
+    }
+    heap::check_user_rate_limit(RateLimitClass::Auth, user_id)?;
+
+    let principal = match login_address {
+        LoginAddress::ICP { .. } => Some(ic_cdk::caller()),
+        _ if bind_to_caller.unwrap_or(false) => Some(guards::caller_not_anonymous()?),
+        _ => None,
+    };
+
+    let session = user_management::create_session(user_id, session_name, principal).await?;
+    Ok((stable::users::get_user(&user_id)?, session))
+}
+
+#[ic_cdk::query]
+fn list_sessions(user_id: u64, token: String) -> Result<Vec<SessionInfo>> {
+    user_management::list_sessions(user_id, &token)
+}
 
-    user_management::set_session(user_id, &Session::new().await?)
+#[ic_cdk::update]
+fn refresh_session(user_id: u64, token: String) -> Result<Session> {
+    user_management::refresh_session(user_id, &token)
 }
 
 #[ic_cdk::update]
-async fn update_password(login_address: LoginAddress, new_password: Option<String>) -> Result<()> {
-    // the token that is passed in the email,
-    // should be generated and stored in the backend canister
-    // and passed down to this function
-    user_management::reset_password_user(login_address, new_password).await
+fn revoke_session(user_id: u64, token: String, session_id: String) -> Result<()> {
+    user_management::revoke_session(user_id, &token, &session_id)
 }
 
 #[ic_cdk::update]
-async fn generate_evm_auth_message(login_address: LoginAddress) -> Result<String> {
+fn revoke_all_sessions(user_id: u64, token: String) -> Result<()> {
+    user_management::revoke_all_sessions(user_id, &token)
+}
+
+#[ic_cdk::query]
+fn get_caller_user() -> Result<User> {
+    stable::users::find_user_by_principal(&ic_cdk::caller())
+}
+
+#[ic_cdk::update]
+async fn request_password_reset(email: String) -> Result<()> {
+    heap::check_rate_limit(RateLimitClass::Auth)?;
+    user_management::request_password_reset(email).await
+}
+
+#[ic_cdk::update]
+async fn confirm_password_reset(email: String, token: String, new_password: String) -> Result<()> {
+    heap::check_rate_limit(RateLimitClass::Auth)?;
+    user_management::confirm_password_reset(email, token, new_password).await
+}
+
+#[ic_cdk::update]
+async fn generate_evm_auth_message(
+    login_address: LoginAddress,
+    chain_id: Option<u64>,
+) -> Result<String> {
     login_address.validate()?;
-    let address = if let LoginAddress::EVM { address } = login_address.clone() {
-        Ok(address)
-    } else {
-        Err(SystemError::InvalidInput(
+    if !matches!(login_address, LoginAddress::EVM { .. }) {
+        return Err(SystemError::InvalidInput(
             "Login address is not of type EVM".to_string(),
-        ))
-    }?;
+        ))?;
+    }
 
+    heap::check_rate_limit(RateLimitClass::Auth)?;
     let user_id = stable::users::find_user_by_login_address(&login_address)?;
-    let auth_message = format!(
-        "Please sign this message to authenticate: {}\nNonce: {}",
-        address,
-        random::generate_token().await?
-    );
+    user_management::generate_auth_message(user_id, &login_address, chain_id).await
+}
+
+#[ic_cdk::update]
+async fn generate_solana_auth_message(login_address: LoginAddress) -> Result<String> {
+    if !matches!(login_address, LoginAddress::Solana { .. }) {
+        return Err(SystemError::InvalidInput(
+            "Login address is not of type Solana".to_string(),
+        ))?;
+    }
+    generate_wallet_auth_message(login_address).await
+}
+
+/// Sign-in challenge for Solana and Bitcoin logins.
+#[ic_cdk::update]
+async fn generate_wallet_auth_message(login_address: LoginAddress) -> Result<String> {
+    login_address.validate()?;
+    if !matches!(
+        login_address,
+        LoginAddress::Solana { .. } | LoginAddress::Bitcoin { .. }
+    ) {
+        return Err(SystemError::InvalidInput(
+            "Login address is not of type Solana or Bitcoin".to_string(),
+        ))?;
+    }
+
+    heap::check_rate_limit(RateLimitClass::Auth)?;
+    let user_id = stable::users::find_user_by_login_address(&login_address)?;
+    user_management::generate_auth_message(user_id, &login_address, None).await
+}
+
+/// Sign-in challenge proving ownership of a wallet that is about to be linked to the user.
+#[ic_cdk::update]
+async fn generate_link_auth_message(
+    user_id: u64,
+    token: String,
+    login_address: LoginAddress,
+    chain_id: Option<u64>,
+) -> Result<String> {
+    heap::check_rate_limit(RateLimitClass::Auth)?;
+    login_address.validate()?;
+    stable::users::get_user(&user_id)?.validate_session(&token)?;
+    heap::check_user_rate_limit(RateLimitClass::Auth, user_id)?;
+    user_management::generate_auth_message(user_id, &login_address, chain_id).await
+}
+
+#[ic_cdk::update]
+async fn link_login_address(
+    user_id: u64,
+    token: String,
+    login_address: LoginAddress,
+    auth_data: Option<AuthenticationData>,
+) -> Result<()> {
+    heap::check_rate_limit(RateLimitClass::Auth)?;
+    stable::users::get_user(&user_id)?.validate_session(&token)?;
+    heap::check_user_rate_limit(RateLimitClass::Auth, user_id)?;
+    user_management::link_login(user_id, &token, login_address, auth_data).await
+}
+
+#[ic_cdk::update]
+async fn request_email_verification(user_id: u64, token: String, email: String) -> Result<()> {
+    heap::check_rate_limit(RateLimitClass::Auth)?;
+    stable::users::get_user(&user_id)?.validate_session(&token)?;
+    heap::check_user_rate_limit(RateLimitClass::Auth, user_id)?;
+    user_management::request_email_verification(user_id, &token, email).await
+}
+
+#[ic_cdk::update]
+async fn confirm_email_verification(
+    user_id: u64,
+    token: String,
+    email: String,
+    code: String,
+    password: String,
+) -> Result<()> {
+    heap::check_rate_limit(RateLimitClass::Auth)?;
+    stable::users::get_user(&user_id)?.validate_session(&token)?;
+    heap::check_user_rate_limit(RateLimitClass::Auth, user_id)?;
+    user_management::confirm_email_verification(user_id, &token, email, code, password).await
+}
 
-    user_management::update_user_auth_message(user_id, &auth_message)?;
+#[ic_cdk::update]
+fn unlink_login_address(user_id: u64, token: String, login_address: LoginAddress) -> Result<()> {
+    user_management::unlink_login(user_id, &token, &login_address)
+}
 
-    Ok(auth_message)
+/// Generates a SIWE message and returns it as EIP-712 typed data, for `eth_signTypedData_v4`.
+/// The typed data is only handed out by the call that creates the message.
+#[ic_cdk::update]
+async fn generate_evm_auth_typed_data(
+    login_address: LoginAddress,
+    chain_id: Option<u64>,
+) -> Result<String> {
+    let message = generate_evm_auth_message(login_address, chain_id).await?;
+    Ok(SiweMessage::parse(&message)?
+        .eip712_typed_data()
+        .to_string())
 }
 
 #[ic_cdk::query]
@@ -525,13 +771,13 @@ fn refetch_user(user_id: u64, token: String) -> Result<User> {
 
 #[ic_cdk::query]
 fn get_user(user_id: u64) -> Result<User> {
-    guards::only_controller()?;
+    guards::only_role(AccessRole::Operator)?;
     stable::users::get_user(&user_id)
 }
 
 #[ic_cdk::update]
 fn remove_user(user_id: u64) -> Result<User> {
-    guards::only_controller()?;
+    guards::only_role(AccessRole::Admin)?;
     stable::users::remove_user(&user_id)
 }
 
@@ -540,8 +786,34 @@ fn add_user_transaction_address(
     user_id: u64,
     token: String,
     address: TransactionAddress,
+    label: Option<String>,
+    make_default: Option<bool>,
+) -> Result<()> {
+    user_management::add_transaction_address(
+        user_id,
+        &token,
+        address,
+        label.unwrap_or_default(),
+        make_default.unwrap_or(false),
+    )
+}
+
+#[ic_cdk::update]
+fn remove_user_transaction_address(
+    user_id: u64,
+    token: String,
+    address: TransactionAddress,
+) -> Result<()> {
+    user_management::remove_transaction_address(user_id, &token, &address)
+}
+
+#[ic_cdk::update]
+fn set_default_transaction_address(
+    user_id: u64,
+    token: String,
+    address: TransactionAddress,
 ) -> Result<()> {
-    user_management::add_transaction_address(user_id, &token, address)
+    user_management::set_default_transaction_address(user_id, &token, &address)
 }
 
 #[ic_cdk::update]
@@ -549,8 +821,9 @@ fn add_user_payment_provider(
     user_id: u64,
     token: String,
     payment_provider: PaymentProvider,
+    label: Option<String>,
 ) -> Result<()> {
-    user_management::add_payment_provider(user_id, &token, payment_provider)
+    user_management::add_payment_provider(user_id, &token, payment_provider, label)
 }
 
 #[ic_cdk::update]
@@ -562,6 +835,100 @@ fn remove_user_payment_provider(
     user_management::remove_payment_provider(user_id, &token, &payment_provider)
 }
 
+#[ic_cdk::update]
+fn set_user_verification_level(user_id: u64, level: VerificationLevel) -> Result<()> {
+    guards::only_role(AccessRole::Operator)?;
+    user_management::set_verification_level(user_id, level)
+}
+
+/// Lets a configured attestor, e.g. a KYC provider canister, record a user's verification level.
+#[ic_cdk::update]
+fn attest_user_verification(user_id: u64, level: VerificationLevel) -> Result<()> {
+    guards::only_attestor()?;
+    user_management::set_verification_level(user_id, level)
+}
+
+#[ic_cdk::update]
+fn suspend_user(user_id: u64, reason: String, duration_secs: u64) -> Result<()> {
+    guards::only_role(AccessRole::Operator)?;
+    moderation_management::suspend_user(user_id, reason, duration_secs)
+}
+
+#[ic_cdk::update]
+fn ban_user(user_id: u64, reason: String) -> Result<()> {
+    guards::only_role(AccessRole::Operator)?;
+    moderation_management::ban_user(user_id, reason)
+}
+
+#[ic_cdk::update]
+fn reinstate_user(user_id: u64, reason: String) -> Result<()> {
+    guards::only_role(AccessRole::Operator)?;
+    moderation_management::reinstate_user(user_id, reason)
+}
+
+#[ic_cdk::query]
+fn get_moderation_log(user_id: Option<u64>) -> Result<Vec<AuditEntry>> {
+    guards::only_role(AccessRole::Auditor)?;
+    Ok(stable::moderation::get_audit_entries(user_id))
+}
+
+#[ic_cdk::update]
+fn submit_appeal(user_id: u64, token: String, message: String) -> Result<()> {
+    moderation_management::submit_appeal(user_id, &token, message)
+}
+
+#[ic_cdk::query]
+fn get_pending_appeals() -> Result<Vec<Appeal>> {
+    guards::only_role(AccessRole::Operator)?;
+    Ok(stable::moderation::get_pending_appeals())
+}
+
+#[ic_cdk::update]
+fn review_appeal(user_id: u64, approve: bool, note: String) -> Result<()> {
+    guards::only_role(AccessRole::Operator)?;
+    moderation_management::review_appeal(user_id, approve, note)
+}
+
+#[ic_cdk::query]
+fn get_user_reputation(user_id: u64) -> Result<UserReputation> {
+    reputation_management::get_user_reputation(user_id)
+}
+
+#[ic_cdk::update]
+fn rate_trade(order_id: u64, user_id: u64, token: String, stars: u8) -> Result<()> {
+    reputation_management::rate_trade(order_id, user_id, &token, stars)
+}
+
+#[ic_cdk::update]
+fn record_dispute_outcome(order_id: u64, winner: UserType) -> Result<()> {
+    guards::only_role(AccessRole::Arbitrator)?;
+    reputation_management::record_dispute_outcome(order_id, winner)
+}
+
+#[ic_cdk::update]
+fn add_user_role(user_id: u64, token: String, role: UserType) -> Result<()> {
+    user_management::add_role(user_id, &token, role)
+}
+
+#[ic_cdk::update]
+fn remove_user_role(user_id: u64, token: String, role: UserType) -> Result<()> {
+    user_management::remove_role(user_id, &token, &role)
+}
+
+#[ic_cdk::query]
+fn get_rate_limit_counters() -> Result<Vec<RateLimitEntry>> {
+    guards::only_role(AccessRole::Auditor)?;
+    Ok(heap::get_rate_limit_entries())
+}
+
+/// Clears the rate limit buckets of `key`, or every bucket when no key is given.
+#[ic_cdk::update]
+fn reset_rate_limits(key: Option<RateLimitKey>) -> Result<()> {
+    guards::only_role(AccessRole::Operator)?;
+    heap::reset_rate_limits(key);
+    Ok(())
+}
+
 // ------------
 // Order Prices
 // ------------
@@ -580,6 +947,16 @@ async fn get_exchange_rate(fiat_symbol: String, crypto_symbol: String) -> Result
     xrc_rates::get_cached_exchange_rate(base_asset, quote_asset).await
 }
 
+#[ic_cdk::query]
+fn get_rejected_rates() -> Vec<RejectedRate> {
+    heap::get_rejected_rates()
+}
+
+#[ic_cdk::query]
+fn get_rate_prefetch_status() -> RatePrefetchStatus {
+    heap::get_rate_prefetch_status()
+}
+
 // <gas, gas_price>
 #[ic_cdk::update]
 async fn get_average_gas_prices(
@@ -615,9 +992,21 @@ async fn calculate_order_evm_fees(
 
 #[ic_cdk::update]
 async fn calculate_order_price(currency: String, crypto: Crypto) -> Result<(u64, u64)> {
+    heap::check_rate_limit(RateLimitClass::Lock)?;
     order_management::calculate_price_and_fee(&currency, &crypto).await
 }
 
+#[ic_cdk::update]
+async fn get_quote(
+    order_id: u64,
+    session_token: String,
+    onramper_user_id: u64,
+    onramper_provider: PaymentProvider,
+) -> Result<OrderQuote> {
+    heap::check_rate_limit(RateLimitClass::Lock)?;
+    order_management::get_quote(order_id, session_token, onramper_user_id, onramper_provider).await
+}
+
 #[ic_cdk::query]
 async fn get_offramper_fee(price: u64) -> u64 {
     price / types::orders::fees::OFFRAMPER_FIAT_FEE_DENOM
@@ -632,13 +1021,20 @@ fn get_orders(
     filter: Option<OrderFilter>,
     page: Option<u32>,
     page_size: Option<u32>,
+    user_token: Option<(u64, String)>,
 ) -> Vec<OrderState> {
-    order_management::get_orders(filter, page, page_size)
+    let viewer = order_management::order_viewer(user_token);
+    order_management::get_orders(filter, page, page_size, &viewer)
+        .into_iter()
+        .map(|order| order.view(&viewer))
+        .collect()
 }
 
+/// Payee details are only returned to the order parties, see `OrderState::view`.
 #[ic_cdk::query]
-fn get_order(order_id: u64) -> Result<OrderState> {
-    memory::stable::orders::get_order(&order_id)
+fn get_order(order_id: u64, user_token: Option<(u64, String)>) -> Result<OrderState> {
+    let viewer = order_management::order_viewer(user_token);
+    Ok(memory::stable::orders::get_order(&order_id)?.view(&viewer))
 }
 
 #[ic_cdk::update]
@@ -649,21 +1045,30 @@ async fn create_order(
     blockchain: Blockchain,
     token_address: Option<String>,
     crypto_amount: u128,
-    offramper_address: TransactionAddress,
+    offramper_address: Option<TransactionAddress>, // defaults to the user's default address for the chain
     offramper_user_id: u64,
     evm_input: Option<EvmOrderInput>,
 ) -> Result<u64> {
+    heap::check_rate_limit(RateLimitClass::Order)?;
     let user = stable::users::get_user(&offramper_user_id)?;
     // user.validate_session(&session_token)?;
-    user.is_banned()?;
-    user.is_offramper()?;
+    user.is_banned(&UserType::Offramper)?;
+    user.validate_role(&UserType::Offramper)?;
+    let offramper_address = user.resolve_address(offramper_address, &blockchain.address_type())?;
 
     for (provider_type, provider) in &offramper_providers {
-        if !user.payment_providers.contains(provider) {
+        if !user.has_payment_account(provider) {
             return Err(UserError::ProviderNotInUser(provider_type.clone()))?;
         }
     }
 
+    let (fiat_amount, _) = order_management::calculate_price_and_fee(
+        &currency,
+        &Crypto::new(blockchain.clone(), token_address.clone(), crypto_amount, 0),
+    )
+    .await?;
+    user.check_trading_limit(&currency, fiat_amount)?;
+
     let tx_hash = order_management::validate_deposit_tx(
         &blockchain,
         evm_input.clone(),
@@ -672,6 +1077,8 @@ async fn create_order(
         token_address.clone(),
     )
     .await?;
+    // the deposit to the offramper's address is what ties the call to them
+    heap::check_user_rate_limit(RateLimitClass::Order, offramper_user_id)?;
 
     let order_id = order_management::create_order(
         &currency,
@@ -695,9 +1102,11 @@ async fn create_order(
 
 #[ic_cdk::update]
 async fn freeze_order(order_id: u64, user_id: u64, session_token: String) -> Result<()> {
+    heap::check_rate_limit(RateLimitClass::Order)?;
     let order = orders::get_order(&order_id)?.created()?;
     let user = memory::stable::users::get_user(&user_id)?;
     user.validate_session(&session_token)?;
+    heap::check_user_rate_limit(RateLimitClass::Order, user_id)?;
     if !order.offramper_user_id == user_id {
         return Err(UserError::Unauthorized.into());
     }
@@ -747,7 +1156,7 @@ async fn top_up_order(
 
 #[ic_cdk::update]
 async fn unprocess_order(order_id: u64) -> Result<()> {
-    guards::only_controller()?;
+    guards::only_role(AccessRole::Operator)?;
     orders::unset_processing_order(&order_id)
 }
 
@@ -757,8 +1166,11 @@ async fn lock_order(
     session_token: String,
     onramper_user_id: u64,
     onramper_provider: PaymentProvider,
-    onramper_address: TransactionAddress,
+    onramper_address: Option<TransactionAddress>, // defaults to the user's default address for the chain
+    quote_id: Option<String>,
+    max_slippage_bps: Option<u32>,
 ) -> Result<()> {
+    heap::check_rate_limit(RateLimitClass::Lock)?;
     orders::set_processing_order(&order_id)?;
 
     if let Err(e) = order_management::lock_order(
@@ -767,6 +1179,8 @@ async fn lock_order(
         onramper_user_id,
         onramper_provider,
         onramper_address,
+        quote_id,
+        max_slippage_bps,
     )
     .await
     {
@@ -779,7 +1193,7 @@ async fn lock_order(
 
 #[ic_cdk::update]
 async fn retry_order_unlock(order_id: u64) -> Result<()> {
-    guards::only_controller()?;
+    guards::only_role(AccessRole::Operator)?;
     orders::set_processing_order(&order_id)?;
 
     if let Err(e) = management::order::unlock_order(order_id).await {
@@ -792,6 +1206,7 @@ async fn retry_order_unlock(order_id: u64) -> Result<()> {
 
 #[ic_cdk::update]
 async fn cancel_order(order_id: u64, session_token: String) -> Result<()> {
+    heap::check_rate_limit(RateLimitClass::Order)?;
     orders::set_processing_order(&order_id)?;
 
     if let Err(e) = order_management::cancel_order(order_id, session_token).await {
@@ -819,9 +1234,14 @@ fn verify_order_is_payable(order_id: u64, session_token: String) -> Result<()> {
     Ok(())
 }
 
+#[ic_cdk::query]
+fn get_payee_details(order_id: u64, session_token: String) -> Result<PaymentProvider> {
+    order_management::get_payee_details(order_id, session_token)
+}
+
 #[ic_cdk::update]
 async fn retry_order_completion(order_id: u64) -> Result<()> {
-    guards::only_controller()?;
+    guards::only_role(AccessRole::Operator)?;
 
     let order = memory::stable::orders::get_order(&order_id)?.locked()?;
     if !order.payment_done {
@@ -843,6 +1263,7 @@ async fn verify_transaction(
         transaction_id
     );
 
+    heap::check_rate_limit(RateLimitClass::Payment)?;
     orders::set_processing_order(&order_id)?;
 
     if let Err(e) = process_transaction(order_id, session_token, transaction_id).await {
@@ -860,7 +1281,7 @@ async fn process_transaction(
 ) -> Result<()> {
     let order = order_management::verify_order_is_payable(order_id, session_token)?;
 
-    match &order.clone().onramper.provider {
+    match &order.onramper.provider {
         PaymentProvider::PayPal { id: onramper_id } => {
             ic_cdk::println!("[verify_transaction] Handling Paypal payment verification");
 
@@ -1,6 +1,10 @@
+pub mod moderation;
 pub mod order;
 pub mod payment;
 pub mod random;
+pub mod rates;
+pub mod reputation;
+pub mod treasury;
 pub mod user;
 pub mod vault;
 
@@ -0,0 +1,116 @@
+use crate::{
+    errors::{Result, SystemError, UserError},
+    model::memory::stable::{moderation, users},
+    types::moderation::{Appeal, AppealStatus, AuditEntry, ModerationAction, ModerationStatus},
+};
+
+fn validate_reason(reason: &str) -> Result<()> {
+    if reason.len() > ModerationStatus::MAX_REASON_LEN {
+        return Err(SystemError::InvalidInput("Moderation reason is too long".to_string()).into());
+    }
+    Ok(())
+}
+
+fn set_status(
+    user_id: u64,
+    status: ModerationStatus,
+    action: ModerationAction,
+    reason: String,
+) -> Result<()> {
+    validate_reason(&reason)?;
+    users::mutate_user(user_id, |user| {
+        user.moderation = status;
+    })?;
+
+    moderation::add_audit_entry(AuditEntry {
+        user_id,
+        action,
+        reason,
+        by: ic_cdk::caller(),
+        timestamp: ic_cdk::api::time(),
+    });
+    Ok(())
+}
+
+pub fn suspend_user(user_id: u64, reason: String, duration_secs: u64) -> Result<()> {
+    let until = ic_cdk::api::time().saturating_add(duration_secs.saturating_mul(1_000_000_000));
+    set_status(
+        user_id,
+        ModerationStatus::Suspended {
+            reason: reason.clone(),
+            by: ic_cdk::caller(),
+            until,
+        },
+        ModerationAction::Suspend { until },
+        reason,
+    )
+}
+
+pub fn ban_user(user_id: u64, reason: String) -> Result<()> {
+    set_status(
+        user_id,
+        ModerationStatus::Banned {
+            reason: reason.clone(),
+            by: ic_cdk::caller(),
+        },
+        ModerationAction::Ban,
+        reason,
+    )
+}
+
+pub fn reinstate_user(user_id: u64, reason: String) -> Result<()> {
+    set_status(
+        user_id,
+        ModerationStatus::Active,
+        ModerationAction::Reinstate,
+        reason,
+    )
+}
+
+pub fn submit_appeal(user_id: u64, token: &str, message: String) -> Result<()> {
+    let user = users::get_user(&user_id)?;
+    user.validate_session(token)?;
+    if user.moderation.is_active(ic_cdk::api::time()) {
+        return Err(UserError::UserNotModerated.into());
+    }
+    if message.is_empty() || message.len() > Appeal::MAX_MESSAGE_LEN {
+        return Err(
+            SystemError::InvalidInput("Appeal message is empty or too long".to_string()).into(),
+        );
+    }
+
+    moderation::submit_appeal(Appeal::new(user_id, message))
+}
+
+/// Resolves a pending appeal. Approving it reinstates the user.
+pub fn review_appeal(user_id: u64, approve: bool, note: String) -> Result<()> {
+    let mut appeal = moderation::get_appeal(user_id)?;
+    if appeal.status != AppealStatus::Pending {
+        return Err(UserError::AppealNotFound.into());
+    }
+    validate_reason(&note)?;
+
+    if approve {
+        set_status(
+            user_id,
+            ModerationStatus::Active,
+            ModerationAction::AppealApproved,
+            note.clone(),
+        )?;
+        appeal.status = AppealStatus::Approved;
+    } else {
+        moderation::add_audit_entry(AuditEntry {
+            user_id,
+            action: ModerationAction::AppealRejected,
+            reason: note.clone(),
+            by: ic_cdk::caller(),
+            timestamp: ic_cdk::api::time(),
+        });
+        appeal.status = AppealStatus::Rejected;
+    }
+
+    appeal.reviewed_by = Some(ic_cdk::caller());
+    appeal.review_note = Some(note);
+    moderation::insert_appeal(appeal);
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap};
 
 use candid::Principal;
 use evm_rpc_canister_types::BlockTag;
@@ -14,21 +14,28 @@ use crate::evm::{
 };
 use crate::icp::vault::Ic2P2ramp as ICPRamp;
 use crate::inter_canister::bitcoin::{self, bitcoin_backend_validate_rune};
-use crate::management::user as user_management;
+use crate::management::{random, user as user_management};
 use crate::model::guards;
 use crate::model::{
     helpers,
-    memory::{self, stable::spent_transactions},
+    memory::{self, heap, stable::spent_transactions},
+};
+use crate::outcalls::xrc_rates::{
+    check_stablecoin_peg, get_cached_exchange_rate, Asset, AssetClass,
 };
-use crate::outcalls::xrc_rates::{get_cached_exchange_rate, Asset, AssetClass};
 use crate::types::{
     self,
+    access::AccessRole,
     evm::{chains, logs::TransactionStatus, token, transaction::TransactionAction},
     icp::{get_icp_token, is_icp_token_supported},
     orders::{
+        ensure_payee_master_key,
         fees::{get_crypto_fee, get_fiat_fee},
-        EvmOrderInput, LockInput, LockedOrder, Order, OrderFilter, OrderState, OrderStateFilter,
+        local_payee_key_allowed, EvmOrderInput, LockInput, LockedOrder, Order, OrderFilter,
+        OrderQuote, OrderState, OrderStateFilter, OrderViewer, DEFAULT_MAX_SLIPPAGE_BPS,
     },
+    rate_limit::RateLimitClass,
+    user::UserType,
     Blockchain, Crypto, PaymentProvider, PaymentProviderType, TransactionAddress,
 };
 
@@ -250,6 +257,7 @@ pub async fn create_order(
         return Err(BlockchainError::FundsTooLow)?;
     }
 
+    ensure_payee_master_key().await?;
     let order = Order::new(
         currency.to_string(),
         offramper_user_id,
@@ -292,26 +300,60 @@ pub async fn topup_order(
     })?
 }
 
+/// Resolves who is reading orders, from the session in `user_token` or else the caller
+/// principal. Invalid sessions and unknown callers get the public view.
+pub fn order_viewer(user_token: Option<(u64, String)>) -> OrderViewer {
+    let caller = ic_cdk::caller();
+    if heap::has_role(&caller, AccessRole::Admin) {
+        return OrderViewer::Admin;
+    }
+
+    let user = match user_token {
+        Some((user_id, token)) => memory::stable::users::get_user(&user_id)
+            .and_then(|user| user.validate_session(&token).map(|_| user)),
+        None if caller != Principal::anonymous() => {
+            memory::stable::users::find_user_by_principal(&caller)
+        }
+        None => return OrderViewer::Public,
+    };
+    user.map_or(OrderViewer::Public, |user| OrderViewer::User(user.id))
+}
+
+/// Whether the offramper of a created order may have it listed, i.e. is not suspended or banned.
+/// Results are cached per offramper for the duration of a listing.
+fn is_listed(order_state: &OrderState, offramper_active: &RefCell<HashMap<u64, bool>>) -> bool {
+    let OrderState::Created(order) = order_state else {
+        return true;
+    };
+    *offramper_active
+        .borrow_mut()
+        .entry(order.offramper_user_id)
+        .or_insert_with(|| {
+            memory::stable::users::get_user(&order.offramper_user_id)
+                .map(|user| user.moderation.is_active(ic_cdk::api::time()))
+                .unwrap_or(false)
+        })
+}
+
+/// Open orders of suspended or banned offrampers are left out of the marketplace listings,
+/// but still returned when filtering by the offramper.
+///
+/// Addresses are redacted from other users' orders, so filtering by address only matches
+/// the orders of the user `viewer` is, or every order for admins.
 pub fn get_orders(
     filter: Option<OrderFilter>,
     page: Option<u32>,
     page_size: Option<u32>,
+    viewer: &OrderViewer,
 ) -> Vec<OrderState> {
+    let offramper_active = RefCell::new(HashMap::new());
+
     match filter {
-        None => memory::stable::storage::ORDERS.with(|p| {
-            let start_index = page.unwrap_or(1).saturating_sub(1) * page_size.unwrap_or(10);
-            let end_index = start_index + page_size.unwrap_or(10);
-
-            p.borrow()
-                .iter()
-                .collect::<Vec<_>>()
-                .into_iter()
-                .rev()
-                .skip(start_index as usize)
-                .take((end_index - start_index) as usize)
-                .map(|(_, v)| v.clone())
-                .collect()
-        }),
+        None => memory::stable::orders::filter_orders(
+            |order_state| is_listed(order_state, &offramper_active),
+            page,
+            page_size,
+        ),
         Some(OrderFilter::ByOfframperId(offramper_id)) => memory::stable::orders::filter_orders(
             |order_state| match order_state {
                 OrderState::Created(order) => order.offramper_user_id == offramper_id,
@@ -331,8 +373,14 @@ pub fn get_orders(
         ),
         Some(OrderFilter::ByOfframperAddress(address)) => memory::stable::orders::filter_orders(
             |order_state| match order_state {
-                OrderState::Created(order) => order.offramper_address == address,
-                OrderState::Locked(order) => order.base.offramper_address == address,
+                OrderState::Created(order) => {
+                    viewer.sees_details_of(order.offramper_user_id)
+                        && order.offramper_address == address
+                }
+                OrderState::Locked(order) => {
+                    viewer.sees_details_of(order.base.offramper_user_id)
+                        && order.base.offramper_address == address
+                }
                 _ => false,
             },
             page,
@@ -340,7 +388,10 @@ pub fn get_orders(
         ),
         Some(OrderFilter::LockedByOnramper(address)) => memory::stable::orders::filter_orders(
             |order_state| match order_state {
-                OrderState::Locked(order) => order.onramper.address == address,
+                OrderState::Locked(order) => {
+                    viewer.sees_details_of(order.onramper.user_id)
+                        && order.onramper.address == address
+                }
                 _ => false,
             },
             page,
@@ -348,20 +399,24 @@ pub fn get_orders(
         ),
         Some(OrderFilter::ByState(state)) => memory::stable::orders::filter_orders(
             |order_state| {
-                matches!(
-                    (state.clone(), order_state),
-                    (OrderStateFilter::Created, OrderState::Created(_))
-                        | (OrderStateFilter::Locked, OrderState::Locked(_))
-                        | (OrderStateFilter::Completed, OrderState::Completed(_))
-                        | (OrderStateFilter::Cancelled, OrderState::Cancelled(_))
-                )
+                is_listed(order_state, &offramper_active)
+                    && matches!(
+                        (state.clone(), order_state),
+                        (OrderStateFilter::Created, OrderState::Created(_))
+                            | (OrderStateFilter::Locked, OrderState::Locked(_))
+                            | (OrderStateFilter::Completed, OrderState::Completed(_))
+                            | (OrderStateFilter::Cancelled, OrderState::Cancelled(_))
+                    )
             },
             page,
             page_size,
         ),
         Some(OrderFilter::ByBlockchain(blockchain)) => memory::stable::orders::filter_orders(
             |order_state| match order_state {
-                OrderState::Created(order) => order.crypto.blockchain == blockchain,
+                OrderState::Created(order) => {
+                    order.crypto.blockchain == blockchain
+                        && is_listed(order_state, &offramper_active)
+                }
                 OrderState::Locked(order) => order.base.crypto.blockchain == blockchain,
                 _ => false,
             },
@@ -371,28 +426,148 @@ pub fn get_orders(
     }
 }
 
+pub async fn get_quote(
+    order_id: u64,
+    session_token: String,
+    onramper_user_id: u64,
+    onramper_provider: PaymentProvider,
+) -> Result<OrderQuote> {
+    memory::stable::users::get_user(&onramper_user_id)?.validate_session(&session_token)?;
+    heap::check_user_rate_limit(RateLimitClass::Lock, onramper_user_id)?;
+    let order = memory::stable::orders::get_order(&order_id)?.created()?;
+
+    if !types::contains_provider_type(&onramper_provider, &order.offramper_providers) {
+        return Err(OrderError::InvalidOnramperProvider)?;
+    }
+
+    let (price, offramper_fee) = calculate_price_and_fee(&order.currency, &order.crypto).await?;
+    let quote = OrderQuote::new(
+        random::generate_token().await?,
+        &order,
+        onramper_user_id,
+        onramper_provider.provider_type(),
+        price,
+        offramper_fee,
+    );
+
+    memory::heap::store_quote(quote.clone());
+    Ok(quote)
+}
+
+/// Returns the price and offramper fee the order should be locked at.
+///
+/// Without a quote the current market price is used. With a quote, the quoted
+/// price is honoured as long as the quote is still valid and the market has not
+/// moved more than `max_slippage_bps` since it was issued. The quote is left in
+/// place, it is only consumed once the lock succeeds.
+async fn get_lock_price(
+    order: &Order,
+    onramper_user_id: u64,
+    onramper_provider: &PaymentProvider,
+    quote_id: Option<&str>,
+    max_slippage_bps: Option<u32>,
+) -> Result<(u64, u64)> {
+    let (price, offramper_fee) = calculate_price_and_fee(&order.currency, &order.crypto).await?;
+
+    let Some(quote_id) = quote_id else {
+        return Ok((price, offramper_fee));
+    };
+
+    let quote = memory::heap::get_quote(quote_id).ok_or(OrderError::QuoteNotFound)?;
+    quote.validate_for(order, onramper_user_id, &onramper_provider.provider_type())?;
+    quote.check_slippage(price, max_slippage_bps.unwrap_or(DEFAULT_MAX_SLIPPAGE_BPS))?;
+
+    Ok((quote.price, quote.offramper_fee))
+}
+
 pub async fn lock_order(
     order_id: u64,
     session_token: String,
     onramper_user_id: u64,
     onramper_provider: PaymentProvider,
-    onramper_address: TransactionAddress,
+    onramper_address: Option<TransactionAddress>,
+    quote_id: Option<String>,
+    max_slippage_bps: Option<u32>,
 ) -> Result<()> {
     let user = memory::stable::users::get_user(&onramper_user_id)?;
     user.validate_session(&session_token)?;
-    user.validate_onramper()?;
-    user.is_banned()?;
+    heap::check_user_rate_limit(RateLimitClass::Lock, onramper_user_id)?;
+    user.validate_role(&UserType::Onramper)?;
+    user.is_banned(&UserType::Onramper)?;
 
     let order = memory::stable::orders::get_order(&order_id)?.created()?;
+    if order.offramper_user_id == onramper_user_id {
+        return Err(OrderError::OwnOrder)?;
+    }
+    let offramper = memory::stable::users::get_user(&order.offramper_user_id)?;
+    if !offramper.moderation.is_active(ic_cdk::api::time()) {
+        return Err(OrderError::OfframperRestricted)?;
+    }
+    let onramper_address =
+        user.resolve_address(onramper_address, &order.crypto.blockchain.address_type())?;
 
     if !types::contains_provider_type(&onramper_provider, &order.offramper_providers) {
         return Err(OrderError::InvalidOnramperProvider)?;
     }
+    if !user.has_payment_account(&onramper_provider) {
+        return Err(UserError::ProviderNotInUser(
+            onramper_provider.provider_type(),
+        ))?;
+    }
 
-    let (price, offramper_fee) = calculate_price_and_fee(&order.currency, &order.crypto).await?;
+    check_stablecoin_peg(&order.crypto.get_symbol()?).await?;
 
+    let (price, offramper_fee) = get_lock_price(
+        &order,
+        onramper_user_id,
+        &onramper_provider,
+        quote_id.as_deref(),
+        max_slippage_bps,
+    )
+    .await?;
+    user.reserve_trading_volume(order_id, &order.currency, price)?;
+    if let Err(e) = offramper.reserve_trading_volume(order_id, &order.currency, price) {
+        memory::stable::volumes::release_volume(onramper_user_id, order_id);
+        return Err(e);
+    }
+
+    let result = commit_lock(
+        order.clone(),
+        price,
+        offramper_fee,
+        onramper_user_id,
+        onramper_provider,
+        onramper_address,
+    )
+    .await;
+    if result.is_err()
+        && matches!(
+            memory::stable::orders::get_order(&order_id),
+            Ok(OrderState::Created(_))
+        )
+    {
+        memory::stable::volumes::release_volume(onramper_user_id, order_id);
+        memory::stable::volumes::release_volume(order.offramper_user_id, order_id);
+    }
+    result?;
+
+    if let Some(quote_id) = quote_id {
+        memory::heap::remove_quote(&quote_id);
+    }
+    Ok(())
+}
+
+async fn commit_lock(
+    order: Order,
+    price: u64,
+    offramper_fee: u64,
+    onramper_user_id: u64,
+    onramper_provider: PaymentProvider,
+    onramper_address: TransactionAddress,
+) -> Result<()> {
+    let order_id = order.id;
     let revolut_consent = payment::get_revolut_consent(
-        order.offramper_providers,
+        order.payee_providers()?,
         &(price as f64 / 100.).to_string(),
         &order.currency,
         &onramper_provider,
@@ -420,7 +595,6 @@ pub async fn lock_order(
                 },
             )
             .await?;
-            Ok(())
         }
         Blockchain::ICP { .. } => {
             memory::stable::orders::lock_order(
@@ -432,7 +606,6 @@ pub async fn lock_order(
                 onramper_address,
                 revolut_consent,
             )?;
-            Ok(())
         }
         Blockchain::Bitcoin => {
             memory::stable::orders::lock_order(
@@ -451,10 +624,10 @@ pub async fn lock_order(
                 order.crypto.amount as u64,
             )
             .await?;
-            Ok(())
         }
         _ => Err(BlockchainError::UnsupportedBlockchain)?,
     }
+    Ok(())
 }
 
 /// Unlocks an order, handling both ICP and EVM blockchain orders.
@@ -502,7 +675,7 @@ pub async fn unlock_order(order_id: u64) -> Result<()> {
     }
 
     let user = memory::stable::users::get_user(&order.onramper.user_id)?;
-    user.validate_onramper()?;
+    user.validate_role(&UserType::Onramper)?;
 
     match order.base.crypto.blockchain {
         Blockchain::EVM { chain_id } => {
@@ -542,8 +715,8 @@ pub async fn unlock_order(order_id: u64) -> Result<()> {
 pub async fn cancel_order(order_id: u64, session_token: String) -> Result<()> {
     let order = memory::stable::orders::get_order(&order_id)?.created()?;
     let user = memory::stable::users::get_user(&order.offramper_user_id)?;
-    user.is_offramper()?;
-    if !user.addresses.contains(&order.offramper_address) {
+    user.validate_role(&UserType::Offramper)?;
+    if !user.has_address(&order.offramper_address) {
         return Err(UserError::Unauthorized.into());
     }
     user.validate_session(&session_token)?;
@@ -606,15 +779,28 @@ pub fn mark_order_as_paid(order_id: u64) -> Result<()> {
             OrderState::Locked(order) => {
                 user_management::update_onramper_payment(
                     order.onramper.user_id,
+                    order_id,
                     order.price,
                     &order.base.currency,
                 )?;
                 user_management::update_offramper_payment(
                     order.base.offramper_user_id,
+                    order_id,
                     order.price,
                     &order.base.currency,
                 )?;
+                let now = ic_cdk::api::time();
+                memory::stable::reputation::mutate_reputation(
+                    order.onramper.user_id,
+                    UserType::Onramper,
+                    |r| {
+                        r.record_lock_to_payment(
+                            now.saturating_sub(order.locked_at) / 1_000_000_000,
+                        )
+                    },
+                );
                 order.payment_done = true;
+                order.paid_at = Some(now);
                 Ok(())
             }
             _ => Err(OrderError::InvalidOrderState(order_state.to_string()))?,
@@ -637,7 +823,25 @@ pub fn set_payment_id(order_id: u64, payment_id: String) -> Result<()> {
 pub fn set_order_completed(order_id: u64) -> Result<()> {
     memory::stable::orders::mutate_order(&order_id, |order_state| match order_state {
         OrderState::Locked(order) => {
-            *order_state = OrderState::Completed(order.clone().complete());
+            let completed = order.clone().complete();
+            memory::stable::reputation::mutate_reputation(
+                order.onramper.user_id,
+                UserType::Onramper,
+                |r| r.record_completed_trade(),
+            );
+            memory::stable::reputation::mutate_reputation(
+                order.base.offramper_user_id,
+                UserType::Offramper,
+                |r| {
+                    r.record_completed_trade();
+                    if let Some(paid_at) = order.paid_at {
+                        r.record_payment_to_release(
+                            completed.completed_at.saturating_sub(paid_at) / 1_000_000_000,
+                        );
+                    }
+                },
+            );
+            *order_state = OrderState::Completed(completed);
             Ok(())
         }
         _ => Err(OrderError::InvalidOrderState(order_state.to_string()))?,
@@ -658,21 +862,46 @@ pub fn verify_order_is_payable(
     if order.uncommited {
         Err(OrderError::OrderUncommitted)?;
     }
+    let user = memory::stable::users::get_user(&order.onramper.user_id)?;
     order
         .base
-        .offramper_providers
-        .get(&order.onramper.provider.provider_type())
-        .ok_or_else(|| UserError::ProviderNotInUser(order.onramper.provider.provider_type()))?;
+        .payee_account(&order.onramper.payment_account(&user)?.provider_type())?;
 
-    let user = memory::stable::users::get_user(&order.onramper.user_id)?;
     if let Some(session_token) = session_token {
         user.validate_session(&session_token)?;
-        user.is_banned()?;
+        user.is_banned(&UserType::Onramper)?;
     } else {
-        guards::only_controller()?;
+        guards::only_role(AccessRole::Operator)?;
     }
 
-    user.validate_onramper()?;
+    user.validate_role(&UserType::Onramper)?;
 
     Ok(order)
 }
+
+/// Encrypts the payee details of open orders created before payee encryption, once the
+/// master key exists. Runs right after an upgrade.
+pub fn setup_legacy_payee_sealing() {
+    if !local_payee_key_allowed() {
+        ic_cdk::println!("[seal_legacy_payees] skipped, payee keys need vetKD on this network");
+        return;
+    }
+
+    ic_cdk_timers::set_timer(std::time::Duration::ZERO, || {
+        ic_cdk::spawn(async {
+            let sealed = match ensure_payee_master_key().await {
+                Ok(()) => memory::stable::orders::seal_legacy_payees(),
+                Err(e) => Err(e),
+            };
+            ic_cdk::println!("[seal_legacy_payees] result = {:?}", sealed);
+        })
+    });
+}
+
+/// Decrypted account the onramper has to pay, only returned to them while their lock holds.
+pub fn get_payee_details(order_id: u64, session_token: String) -> Result<PaymentProvider> {
+    let order = verify_order_is_payable(order_id, Some(session_token))?;
+    order
+        .base
+        .payee_account(&order.onramper.provider.provider_type())
+}
@@ -37,14 +37,9 @@ pub async fn verify_paypal_payment(
     let currency_matches =
         capture_details.purchase_units[0].amount.currency_code == order.base.currency;
 
-    let offramper_provider = order
-        .base
-        .offramper_providers
-        .iter()
-        .find(|(provider_type, _)| *provider_type == &PaymentProviderType::PayPal)
-        .ok_or(OrderError::InvalidOfframperProvider)?;
+    let offramper_provider = order.base.payee_account(&PaymentProviderType::PayPal)?;
 
-    let PaymentProvider::PayPal { id: offramper_id } = offramper_provider.1 else {
+    let PaymentProvider::PayPal { id: offramper_id } = &offramper_provider else {
         return Err(OrderError::InvalidOfframperProvider)?;
     };
 
@@ -91,18 +86,13 @@ pub async fn verify_revolut_payment(
 
     let offramper_account = payment_details.data.initiation.creditor_account;
 
-    let offramper_provider = order
-        .base
-        .offramper_providers
-        .iter()
-        .find(|(provider_type, _)| *provider_type == &PaymentProviderType::Revolut)
-        .ok_or(OrderError::InvalidOfframperProvider)?;
+    let offramper_provider = order.base.payee_account(&PaymentProviderType::Revolut)?;
 
     let PaymentProvider::Revolut {
         scheme: offramper_scheme,
         id: offramper_id,
         name: offramper_name,
-    } = offramper_provider.1
+    } = &offramper_provider
     else {
         return Err(OrderError::InvalidOfframperProvider)?;
     };
@@ -0,0 +1,94 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::errors::{RampError, SystemError};
+use crate::model::memory::heap::{self, read_state};
+use crate::outcalls::xrc_rates::{self, Asset, AssetClass, ExchangeRateError, XRC_CALL_CYCLES};
+
+pub fn setup_rate_prefetch_timer() {
+    let interval_secs = read_state(|s| s.rate_prefetch.interval_secs);
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(interval_secs), || {
+        ic_cdk::spawn(prefetch_active_rates())
+    });
+}
+
+/// Clears the running flag when a prefetch run ends, including when the
+/// future is dropped because the run trapped.
+struct PrefetchGuard;
+
+impl Drop for PrefetchGuard {
+    fn drop(&mut self) {
+        heap::mutate_rate_prefetch_status(|status| status.finish());
+    }
+}
+
+/// Refreshes the cached rates of the active trading pairs before they expire, so that
+/// users do not have to wait for an XRC call when quoting or locking orders.
+pub async fn prefetch_active_rates() {
+    let config = read_state(|s| s.rate_prefetch.clone());
+    if !config.enabled {
+        return;
+    }
+
+    let now = ic_cdk::api::time();
+    if !heap::mutate_rate_prefetch_status(|status| status.start(now)) {
+        return;
+    }
+    let _guard = PrefetchGuard;
+
+    let margin = config.refresh_margin_secs * 1_000_000_000;
+    let mut seen = HashSet::new();
+    let mut pairs = Vec::new();
+    for (symbol, currency) in heap::get_active_trading_pairs() {
+        let base_asset = Asset {
+            class: AssetClass::Cryptocurrency,
+            symbol,
+        };
+        let quote_asset = Asset {
+            class: AssetClass::FiatCurrency,
+            symbol: currency,
+        };
+        for pair in xrc_rates::get_expiring_pairs(base_asset, quote_asset, margin) {
+            if seen.insert((pair.0.symbol.clone(), pair.1.symbol.clone())) {
+                pairs.push(pair);
+            }
+        }
+    }
+
+    for (base_asset, quote_asset) in pairs {
+        let charged = heap::mutate_rate_prefetch_status(|status| {
+            status.charge(XRC_CALL_CYCLES, config.daily_cycles_budget)
+        });
+        if !charged {
+            ic_cdk::println!("[prefetch_active_rates] daily cycles budget exhausted");
+            break;
+        }
+
+        match xrc_rates::refresh_exchange_rate(base_asset.clone(), quote_asset.clone()).await {
+            Ok(_) => heap::mutate_rate_prefetch_status(|status| status.reset_backoff()),
+            Err(RampError::SystemError(SystemError::ExchangeRateError(
+                ExchangeRateError::RateLimited | ExchangeRateError::Pending,
+            ))) => {
+                ic_cdk::println!(
+                    "[prefetch_active_rates] XRC busy while fetching {}/{}, backing off",
+                    base_asset.symbol,
+                    quote_asset.symbol
+                );
+                heap::mutate_rate_prefetch_status(|status| {
+                    status.back_off(
+                        ic_cdk::api::time(),
+                        config.backoff_base_secs,
+                        config.max_backoff_secs,
+                    )
+                });
+                break;
+            }
+            Err(e) => ic_cdk::println!(
+                "[prefetch_active_rates] failed to refresh {}/{}: {:?}",
+                base_asset.symbol,
+                quote_asset.symbol,
+                e
+            ),
+        }
+    }
+}
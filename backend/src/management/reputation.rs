@@ -0,0 +1,76 @@
+use crate::{
+    errors::{OrderError, Result, UserError},
+    model::memory::stable::{orders, reputation, users},
+    types::{
+        orders::OrderState,
+        reputation::{RoleReputation, UserReputation},
+        user::UserType,
+    },
+};
+
+pub const MAX_RATING: u8 = 5;
+
+pub fn get_user_reputation(user_id: u64) -> Result<UserReputation> {
+    let user = users::get_user(&user_id)?;
+    let reputations = [UserType::Onramper, UserType::Offramper]
+        .into_iter()
+        .map(|role| {
+            let reputation = RoleReputation::from(&reputation::get_reputation(user_id, &role));
+            (role, reputation)
+        })
+        .collect();
+
+    Ok(UserReputation {
+        user_id,
+        roles: user.roles,
+        verification_level: user.verification_level,
+        scores: user.scores,
+        reputations,
+    })
+}
+
+/// Lets either party of a completed trade rate the counterparty from 1 to `MAX_RATING`.
+pub fn rate_trade(order_id: u64, user_id: u64, token: &str, stars: u8) -> Result<()> {
+    if !(1..=MAX_RATING).contains(&stars) {
+        return Err(UserError::InvalidRating.into());
+    }
+    users::get_user(&user_id)?.validate_session(token)?;
+
+    let order = match orders::get_order(&order_id)? {
+        OrderState::Completed(order) => order,
+        order_state => Err(OrderError::InvalidOrderState(order_state.to_string()))?,
+    };
+    let (Some(onramper_id), Some(offramper_id)) = (order.onramper_user_id, order.offramper_user_id)
+    else {
+        return Err(OrderError::TradeNotRateable)?;
+    };
+
+    let (counterparty, counterparty_role) = if user_id == onramper_id {
+        (offramper_id, UserType::Offramper)
+    } else if user_id == offramper_id {
+        (onramper_id, UserType::Onramper)
+    } else {
+        return Err(UserError::Unauthorized)?;
+    };
+
+    reputation::add_rating(order_id, user_id, counterparty, counterparty_role, stars)
+}
+
+/// Records the outcome of a dispute over a locked or completed order, once per order.
+pub fn record_dispute_outcome(order_id: u64, winner: UserType) -> Result<()> {
+    let (onramper_id, offramper_id) = match orders::get_order(&order_id)? {
+        OrderState::Locked(order) => (order.onramper.user_id, order.base.offramper_user_id),
+        OrderState::Completed(order) => match (order.onramper_user_id, order.offramper_user_id) {
+            (Some(onramper_id), Some(offramper_id)) => (onramper_id, offramper_id),
+            _ => return Err(OrderError::TradeNotRateable)?,
+        },
+        order_state => Err(OrderError::InvalidOrderState(order_state.to_string()))?,
+    };
+
+    reputation::add_dispute_outcome(
+        order_id,
+        onramper_id,
+        offramper_id,
+        winner == UserType::Onramper,
+    )
+}
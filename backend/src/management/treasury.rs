@@ -0,0 +1,189 @@
+use icrc_ledger_types::icrc1::{account::Account, transfer::NumTokens};
+
+use crate::{
+    errors::{Result, SystemError, UserError},
+    evm::vault::Ic2P2ramp,
+    icp::vault::Ic2P2ramp as ICPRamp,
+    model::{
+        helpers,
+        memory::{
+            heap::{self, read_state},
+            stable::treasury,
+        },
+    },
+    types::{
+        access::AccessRole,
+        evm::token,
+        icp::get_icp_token,
+        treasury::{ProposalStatus, TreasuryAction, TreasuryProposal},
+    },
+};
+
+fn only_signer() -> Result<()> {
+    if read_state(|s| s.treasury.signers.contains(&ic_cdk::caller())) {
+        Ok(())
+    } else {
+        Err(UserError::NotTreasurySigner.into())
+    }
+}
+
+fn validate_action(action: &TreasuryAction) -> Result<()> {
+    match action {
+        TreasuryAction::CanisterTransfer {
+            ledger_canister,
+            amount,
+            ..
+        } => {
+            let fee = get_icp_token(ledger_canister)?.fee;
+            if fee >= *amount {
+                return Err(SystemError::InvalidInput(
+                    "Amount does not cover the ledger fee".to_string(),
+                )
+                .into());
+            }
+            Ok(())
+        }
+        TreasuryAction::EvmTransfer {
+            chain_id,
+            to,
+            token,
+            ..
+        } => {
+            helpers::validate_evm_address(to)?;
+            if let Some(token) = token {
+                token::evm_token_is_approved(*chain_id, token)?;
+            }
+            Ok(())
+        }
+        TreasuryAction::EvmFeeWithdrawal {
+            chain_id, token, ..
+        } => {
+            if let Some(token) = token {
+                token::evm_token_is_approved(*chain_id, token)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Records a proposal for `action`, approved by the proposer if they are a signer.
+pub fn propose(action: TreasuryAction) -> Result<u64> {
+    let config = read_state(|s| s.treasury.clone());
+    config.validate()?;
+    validate_action(&action)?;
+
+    let now = ic_cdk::api::time();
+    let mut proposal = TreasuryProposal::new(
+        treasury::next_proposal_id(),
+        action,
+        ic_cdk::caller(),
+        &config,
+        now,
+    );
+    if config.signers.contains(&proposal.proposer) {
+        proposal.approve(proposal.proposer, config.timelock_secs, now)?;
+    }
+
+    let proposal_id = proposal.id;
+    treasury::insert_proposal(proposal);
+    Ok(proposal_id)
+}
+
+pub fn approve(proposal_id: u64) -> Result<()> {
+    only_signer()?;
+    let timelock_secs = read_state(|s| s.treasury.timelock_secs);
+
+    treasury::mutate_proposal(proposal_id, |proposal| {
+        proposal.approve(ic_cdk::caller(), timelock_secs, ic_cdk::api::time())
+    })
+}
+
+/// Proposals can be cancelled by their proposer or an admin until they are executed.
+pub fn cancel(proposal_id: u64) -> Result<()> {
+    let caller = ic_cdk::caller();
+    let is_admin = heap::has_role(&caller, AccessRole::Admin);
+
+    treasury::mutate_proposal(proposal_id, |proposal| {
+        if proposal.proposer != caller && !is_admin {
+            return Err(UserError::Unauthorized.into());
+        }
+        if !matches!(
+            proposal.status,
+            ProposalStatus::Pending | ProposalStatus::Approved { .. }
+        ) {
+            return Err(UserError::ProposalNotPending.into());
+        }
+        proposal.status = ProposalStatus::Cancelled { by: caller };
+        Ok(())
+    })
+}
+
+/// Executes an approved proposal once its timelock has passed. A failed execution is
+/// recorded and has to be proposed again.
+pub async fn execute(proposal_id: u64) -> Result<()> {
+    // moved out of `Approved` before awaiting, so that concurrent calls cannot execute twice
+    let config = read_state(|s| s.treasury.clone());
+    let proposal = treasury::mutate_proposal(proposal_id, |proposal| {
+        proposal.check_executable(&config, ic_cdk::api::time())?;
+        proposal.status = ProposalStatus::Executing;
+        Ok(proposal.clone())
+    })?;
+
+    let result = execute_action(proposal.action).await;
+    let at = ic_cdk::api::time();
+    treasury::mutate_proposal(proposal_id, |proposal| {
+        proposal.status = match &result {
+            Ok(()) => ProposalStatus::Executed { at },
+            Err(e) => ProposalStatus::Failed {
+                at,
+                error: e.to_string(),
+            },
+        };
+        Ok(())
+    })?;
+
+    result
+}
+
+async fn execute_action(action: TreasuryAction) -> Result<()> {
+    match action {
+        TreasuryAction::CanisterTransfer {
+            ledger_canister,
+            to_principal,
+            amount,
+        } => {
+            // the ledger fee may have changed since the proposal was validated
+            let fee = get_icp_token(&ledger_canister)?.fee;
+            let amount = NumTokens::from(amount);
+            if amount <= fee {
+                return Err(SystemError::InvalidInput(
+                    "Amount does not cover the ledger fee".to_string(),
+                ))?;
+            }
+            let to_account = Account {
+                owner: to_principal,
+                subaccount: None,
+            };
+
+            ICPRamp::transfer(ledger_canister, to_account, amount - fee.clone(), Some(fee)).await?;
+            Ok(())
+        }
+        TreasuryAction::EvmTransfer {
+            chain_id,
+            to,
+            amount,
+            token,
+            estimated_gas,
+        } => Ic2P2ramp::transfer(chain_id, &to, amount, token, estimated_gas).await,
+        TreasuryAction::EvmFeeWithdrawal {
+            chain_id,
+            amount,
+            token,
+        } => {
+            let canister_address =
+                read_state(|s| s.evm_address.clone()).expect("evm address should be initialized");
+            Ic2P2ramp::withdraw_deposit(chain_id, 0, canister_address, token, amount, 0).await?;
+            Ok(())
+        }
+    }
+}
@@ -1,15 +1,25 @@
 use std::collections::HashSet;
 
+use candid::Principal;
+
 use super::random;
 use crate::{
     model::{
         errors::{Result, SystemError, UserError},
-        memory::stable::users,
+        memory::{
+            heap,
+            stable::{orders, sessions, users, volumes},
+        },
     },
+    outcalls::mail,
     types::{
-        session::Session,
+        evm::siwe::SiweMessage,
+        limits::VerificationLevel,
+        mail::{EmailVerification, PasswordReset},
+        session::{Session, SessionInfo},
         user::{User, UserType},
-        LoginAddress, PaymentProvider, TransactionAddress,
+        wallet_auth::WalletAuthMessage,
+        AuthenticationData, LoginAddress, PaymentProvider, TransactionAddress,
     },
 };
 
@@ -20,6 +30,9 @@ pub async fn register_user(
     password: Option<String>,
 ) -> Result<User> {
     login_address.validate()?;
+    if users::find_user_by_login_address(&login_address).is_ok() {
+        return Err(UserError::LoginAlreadyLinked)?;
+    }
 
     let hashed_password: Result<Option<String>> = match login_address.clone() {
         LoginAddress::Email { .. } => {
@@ -47,27 +60,57 @@ pub async fn register_user(
         .try_for_each(|p| p.validate())?;
 
     let mut user = User::new(user_type, login_address, hashed_password?)?;
-    user.payment_providers = payment_providers;
+    for provider in payment_providers {
+        let label = format!("{:?}", provider.provider_type());
+        user.add_payment_account(provider, label)?;
+    }
 
-    users::insert_user(&user);
+    users::insert_user(&user)?;
     Ok(user)
 }
 
-pub async fn reset_password_user(
-    login_address: LoginAddress,
-    new_password: Option<String>,
-) -> Result<()> {
+pub async fn request_password_reset(email: String) -> Result<()> {
+    let login_address = LoginAddress::Email {
+        email: email.clone(),
+    };
     login_address.validate()?;
-    let hashed_password = if let LoginAddress::Email { .. } = login_address {
-        let password = new_password.ok_or(UserError::PasswordRequired)?;
-        random::hash_password(&password).await?
-    } else {
-        return Err(SystemError::InvalidInput(
-            "Login Address must be of type Email".to_string(),
-        ))?;
+    mail::ensure_mail_configured()?;
+    if users::find_user_by_login_address(&login_address).is_err() {
+        // do not reveal whether the email is registered
+        return Ok(());
+    }
+
+    let token = random::generate_token().await?;
+    let hashed_token = random::hash_password(&token).await?;
+    heap::store_password_reset(&login_address.index_key(), PasswordReset::new(hashed_token));
+
+    mail::send_password_reset_email(&email, &token).await
+}
+
+pub async fn confirm_password_reset(
+    email: String,
+    token: String,
+    new_password: String,
+) -> Result<()> {
+    let login_address = LoginAddress::Email {
+        email: email.clone(),
     };
+    login_address.validate()?;
 
-    users::reset_password_user(&login_address, hashed_password)?;
+    // only removed once the token matches, so that a wrong guess cannot cancel someone's reset
+    let reset_key = login_address.index_key();
+    let reset = heap::get_password_reset(&reset_key).ok_or(UserError::TokenInvalid)?;
+    if reset.is_expired() {
+        return Err(UserError::TokenExpired)?;
+    }
+    if !random::verify_password(&token, &reset.hashed_token)? {
+        return Err(UserError::TokenInvalid)?;
+    }
+    heap::remove_password_reset(&reset_key);
+
+    let hashed_password = random::hash_password(&new_password).await?;
+    let user_id = users::reset_password_user(&login_address, hashed_password)?;
+    sessions::revoke_all_sessions(user_id);
     Ok(())
 }
 
@@ -75,18 +118,39 @@ pub fn add_transaction_address(
     user_id: u64,
     token: &str,
     address: TransactionAddress,
+    label: String,
+    make_default: bool,
 ) -> Result<()> {
     address.validate()?;
 
     users::mutate_user(user_id, |user| {
         user.validate_session(token)?;
 
-        if let Some(existing_address) = user.addresses.take(&address) {
-            ic_cdk::println!("updating address {:?} to {:?}", existing_address, address)
-        }
+        user.add_address(address, label, make_default)
+    })?
+}
 
-        user.addresses.insert(address);
-        Ok(())
+pub fn remove_transaction_address(
+    user_id: u64,
+    token: &str,
+    address: &TransactionAddress,
+) -> Result<()> {
+    users::mutate_user(user_id, |user| {
+        user.validate_session(token)?;
+
+        user.remove_address(address)
+    })?
+}
+
+pub fn set_default_transaction_address(
+    user_id: u64,
+    token: &str,
+    address: &TransactionAddress,
+) -> Result<()> {
+    users::mutate_user(user_id, |user| {
+        user.validate_session(token)?;
+
+        user.set_default_address(address)
     })?
 }
 
@@ -94,13 +158,15 @@ pub fn add_payment_provider(
     user_id: u64,
     token: &str,
     payment_provider: PaymentProvider,
+    label: Option<String>,
 ) -> Result<()> {
     payment_provider.validate()?;
+    let label = label.unwrap_or_else(|| format!("{:?}", payment_provider.provider_type()));
 
     users::mutate_user(user_id, |user| {
         user.validate_session(token)?;
 
-        user.payment_providers.insert(payment_provider);
+        user.add_payment_account(payment_provider, label)?;
         Ok(())
     })?
 }
@@ -113,33 +179,233 @@ pub fn remove_payment_provider(
     users::mutate_user(user_id, |user| {
         user.validate_session(token)?;
 
-        user.payment_providers.remove(payment_provider);
+        user.remove_payment_account(&payment_provider.account_id())
+    })?
+}
+
+pub fn add_role(user_id: u64, token: &str, role: UserType) -> Result<()> {
+    users::mutate_user(user_id, |user| {
+        user.validate_session(token)?;
+
+        user.add_role(role);
         Ok(())
     })?
 }
 
+/// Removes a role, unless the user still has created or locked orders in it, which
+/// could otherwise not be unlocked or cancelled anymore.
+pub fn remove_role(user_id: u64, token: &str, role: &UserType) -> Result<()> {
+    users::get_user(&user_id)?.validate_session(token)?;
+    if orders::has_open_orders(user_id, role) {
+        return Err(UserError::RoleHasOpenOrders.into());
+    }
+
+    users::mutate_user(user_id, |user| user.remove_role(role))?
+}
+
+/// Links `login_address` to the user. Wallet logins are proven with a signature over the
+/// pending auth message and Internet Identity by the caller principal. Emails are linked
+/// through `request_email_verification` and `confirm_email_verification` instead.
+pub async fn link_login(
+    user_id: u64,
+    token: &str,
+    login_address: LoginAddress,
+    auth_data: Option<AuthenticationData>,
+) -> Result<()> {
+    login_address.validate()?;
+    if users::find_user_by_login_address(&login_address).is_ok() {
+        return Err(UserError::LoginAlreadyLinked)?;
+    }
+
+    let user = users::get_user(&user_id)?;
+    user.validate_session(token)?;
+
+    if let LoginAddress::Email { .. } = login_address {
+        return Err(UserError::EmailNotVerified)?;
+    }
+
+    let auth_message = take_auth_message(user_id)?;
+    if let Err(e) = user.verify_login(&login_address, auth_data).await {
+        restore_auth_message(user_id, auth_message)?;
+        return Err(e);
+    }
+
+    // the login index rejects the login if another user linked it while verification awaited
+    users::mutate_user(user_id, |user| user.link_login(login_address, None))?
+}
+
+/// Emails a code proving that the user controls `email`, before it can be linked as a login.
+pub async fn request_email_verification(user_id: u64, token: &str, email: String) -> Result<()> {
+    let login_address = LoginAddress::Email {
+        email: email.clone(),
+    };
+    login_address.validate()?;
+    mail::ensure_mail_configured()?;
+    users::get_user(&user_id)?.validate_session(token)?;
+    if users::find_user_by_login_address(&login_address).is_ok() {
+        return Err(UserError::LoginAlreadyLinked)?;
+    }
+
+    let code = random::generate_token().await?;
+    let hashed_code = random::hash_password(&code).await?;
+    heap::store_email_verification(
+        &login_address.index_key(),
+        user_id,
+        EmailVerification::new(hashed_code),
+    );
+
+    mail::send_email_verification_email(&email, &code).await
+}
+
+/// Links `email` with its own `password` once the emailed code is confirmed.
+pub async fn confirm_email_verification(
+    user_id: u64,
+    token: &str,
+    email: String,
+    code: String,
+    password: String,
+) -> Result<()> {
+    let login_address = LoginAddress::Email { email };
+    login_address.validate()?;
+    users::get_user(&user_id)?.validate_session(token)?;
+
+    let email_key = login_address.index_key();
+    let verification =
+        heap::get_email_verification(&email_key, user_id).ok_or(UserError::TokenInvalid)?;
+    if verification.is_expired() {
+        return Err(UserError::TokenExpired)?;
+    }
+    if !random::verify_password(&code, &verification.hashed_token)? {
+        return Err(UserError::TokenInvalid)?;
+    }
+    heap::remove_email_verification(&email_key, user_id);
+
+    let hashed_password = random::hash_password(&password).await?;
+    // the login index rejects the email if another user linked it meanwhile
+    users::mutate_user(user_id, |user| {
+        user.link_login(login_address, Some(hashed_password))
+    })?
+}
+
+pub fn unlink_login(user_id: u64, token: &str, login_address: &LoginAddress) -> Result<()> {
+    users::mutate_user(user_id, |user| {
+        user.validate_session(token)?;
+
+        user.unlink_login(login_address)
+    })?
+}
+
+/// Creates a sign-in challenge for a wallet login and stores it as the user's pending message.
+pub async fn generate_auth_message(
+    user_id: u64,
+    login_address: &LoginAddress,
+    chain_id: Option<u64>,
+) -> Result<String> {
+    let config = heap::read_state(|s| s.siwe.clone());
+    let nonce = random::generate_token().await?;
+
+    let auth_message = match login_address {
+        LoginAddress::EVM { address } => {
+            SiweMessage::new(&config, address, chain_id.unwrap_or(1), nonce)?.to_string()
+        }
+        LoginAddress::Solana { address } => {
+            WalletAuthMessage::new(&config, "Solana", address, nonce).to_string()
+        }
+        LoginAddress::Bitcoin { address } => {
+            WalletAuthMessage::new(&config, "Bitcoin", address, nonce).to_string()
+        }
+        _ => {
+            return Err(SystemError::InvalidInput(
+                "Login address is not a wallet".to_string(),
+            ))?
+        }
+    };
+
+    update_user_auth_message(user_id, &auth_message)?;
+    Ok(auth_message)
+}
+
 pub fn update_user_auth_message(user_id: u64, auth_message: &str) -> Result<()> {
     users::mutate_user(user_id, |user| {
         user.evm_auth_message = Some(auth_message.to_string());
     })
 }
 
-pub fn set_session(user_id: u64, session: &Session) -> Result<User> {
+/// Takes the pending auth message out of the user while its signature is verified, so that
+/// a concurrent call cannot replay the signature. It is gone for good once the login succeeds.
+pub fn take_auth_message(user_id: u64) -> Result<Option<String>> {
+    users::mutate_user(user_id, |user| user.evm_auth_message.take())
+}
+
+/// Puts back a message taken for a failed verification, so that a bad signature sent by
+/// someone else does not void the user's pending sign-in. A newer message is kept.
+pub fn restore_auth_message(user_id: u64, auth_message: Option<String>) -> Result<()> {
     users::mutate_user(user_id, |user| {
-        user.session = Some(session.clone());
-        Ok(user.to_owned())
-    })?
+        if user.evm_auth_message.is_none() {
+            user.evm_auth_message = auth_message;
+        }
+    })
+}
+
+pub async fn create_session(
+    user_id: u64,
+    name: Option<String>,
+    principal: Option<Principal>,
+) -> Result<Session> {
+    let session = Session::new(name.unwrap_or_else(|| "default".to_string()), principal).await?;
+    sessions::add_session(user_id, session.clone());
+    Ok(session)
+}
+
+pub fn list_sessions(user_id: u64, token: &str) -> Result<Vec<SessionInfo>> {
+    users::get_user(&user_id)?.validate_session(token)?;
+    Ok(sessions::list_sessions(user_id))
+}
+
+pub fn refresh_session(user_id: u64, token: &str) -> Result<Session> {
+    sessions::refresh_session(user_id, token)
 }
 
-pub fn update_onramper_payment(user_id: u64, fiat_amount: u64, currency: &str) -> Result<()> {
+pub fn revoke_session(user_id: u64, token: &str, session_id: &str) -> Result<()> {
+    users::get_user(&user_id)?.validate_session(token)?;
+    sessions::revoke_session(user_id, session_id)
+}
+
+pub fn revoke_all_sessions(user_id: u64, token: &str) -> Result<()> {
+    users::get_user(&user_id)?.validate_session(token)?;
+    sessions::revoke_all_sessions(user_id);
+    Ok(())
+}
+
+pub fn update_onramper_payment(
+    user_id: u64,
+    order_id: u64,
+    fiat_amount: u64,
+    currency: &str,
+) -> Result<()> {
     users::mutate_user(user_id, |user| {
         user.update_fiat_amount(fiat_amount, currency);
-        user.increase_score();
-    })
+        user.increase_score(&UserType::Onramper);
+    })?;
+    volumes::record_volume(user_id, Some(order_id), currency, fiat_amount);
+    Ok(())
 }
 
-pub fn update_offramper_payment(user_id: u64, fiat_amount: u64, currency: &str) -> Result<()> {
+pub fn update_offramper_payment(
+    user_id: u64,
+    order_id: u64,
+    fiat_amount: u64,
+    currency: &str,
+) -> Result<()> {
     users::mutate_user(user_id, |user| {
         user.update_fiat_amount(fiat_amount, currency)
+    })?;
+    volumes::record_volume(user_id, Some(order_id), currency, fiat_amount);
+    Ok(())
+}
+
+pub fn set_verification_level(user_id: u64, level: VerificationLevel) -> Result<()> {
+    users::mutate_user(user_id, |user| {
+        user.verification_level = level;
     })
 }
@@ -4,7 +4,10 @@ use candid::CandidType;
 use ic_cdk::api::call::RejectionCode;
 use thiserror::Error;
 
-use crate::{outcalls::xrc_rates::ExchangeRateError, types::PaymentProviderType};
+use crate::{
+    outcalls::xrc_rates::ExchangeRateError,
+    types::{access::AccessRole, PaymentProviderType},
+};
 
 pub type Result<T> = std::result::Result<T, RampError>;
 
@@ -46,6 +49,12 @@ pub enum UserError {
     #[error("Signature is not valid")]
     InvalidSignature,
 
+    #[error("Authentication message is not valid")]
+    InvalidAuthMessage,
+
+    #[error("Authentication message is expired")]
+    AuthMessageExpired,
+
     #[error("Token is Invalid")]
     TokenInvalid,
 
@@ -55,6 +64,9 @@ pub enum UserError {
     #[error("Session not Found")]
     SessionNotFound,
 
+    #[error("Session is bound to a different principal")]
+    SessionPrincipalMismatch,
+
     #[error("User Not Found")]
     UserNotFound,
 
@@ -67,6 +79,99 @@ pub enum UserError {
     #[error("User score below zero")]
     UserBanned,
 
+    #[error("User must keep at least one role")]
+    CannotRemoveLastRole,
+
+    #[error("Role has open orders")]
+    RoleHasOpenOrders,
+
+    #[error("Login method is already linked to a user")]
+    LoginAlreadyLinked,
+
+    #[error("A user can link at most {0} logins")]
+    TooManyLogins(usize),
+
+    #[error("A user can save at most {0} addresses")]
+    TooManyAddresses(usize),
+
+    #[error("A user can save at most {0} payment accounts")]
+    TooManyPaymentAccounts(usize),
+
+    #[error("Email must be verified with the code sent to it")]
+    EmailNotVerified,
+
+    #[error("Login method is not linked to the user")]
+    LoginNotLinked,
+
+    #[error("User must keep at least one login method")]
+    CannotRemoveLastLogin,
+
+    #[error("Daily limit of {limit} {currency} cents exceeded")]
+    DailyLimitExceeded { currency: String, limit: u64 },
+
+    #[error("Monthly limit of {limit} {currency} cents exceeded")]
+    MonthlyLimitExceeded { currency: String, limit: u64 },
+
+    #[error("No trading limit is configured for {0}")]
+    TradingLimitNotConfigured(String),
+
+    #[error("Caller is not a verification attestor")]
+    NotAttestor,
+
+    #[error("Rating must be between 1 and 5")]
+    InvalidRating,
+
+    #[error("User is suspended until {until}: {reason}")]
+    UserSuspended { until: u64, reason: String },
+
+    #[error("User is banned: {reason}")]
+    UserBannedByAdmin { reason: String },
+
+    #[error("User is neither suspended nor banned")]
+    UserNotModerated,
+
+    #[error("An appeal is already pending")]
+    AppealAlreadyPending,
+
+    #[error("Appeal not found")]
+    AppealNotFound,
+
+    #[error("Address is not saved for the user")]
+    AddressNotInUser,
+
+    #[error("Payment account not found")]
+    PaymentAccountNotFound,
+
+    #[error("Too many requests, retry in {retry_after_secs} seconds")]
+    RateLimited { retry_after_secs: u64 },
+
+    #[error("Caller lacks the {0:?} role")]
+    MissingRole(AccessRole),
+
+    #[error("Treasury signers or threshold are not configured")]
+    TreasuryNotConfigured,
+
+    #[error("Caller is not a treasury signer")]
+    NotTreasurySigner,
+
+    #[error("Treasury proposal not found")]
+    ProposalNotFound,
+
+    #[error("Treasury proposal is not pending")]
+    ProposalNotPending,
+
+    #[error("Treasury proposal has expired")]
+    ProposalExpired,
+
+    #[error("Treasury proposal was already approved by the caller")]
+    ProposalAlreadyApproved,
+
+    #[error("Treasury proposal has not been approved")]
+    ProposalNotApproved,
+
+    #[error("Treasury proposal is timelocked until {until}")]
+    ProposalTimelocked { until: u64 },
+
     #[error("Provider is Not Defined for User {:?}", .0)]
     ProviderNotInUser(PaymentProviderType),
 }
@@ -111,6 +216,45 @@ pub enum OrderError {
 
     #[error("Payment Verification Failed")]
     PaymentVerificationFailed,
+
+    #[error("Quote Not Found")]
+    QuoteNotFound,
+
+    #[error("Quote does not match the order")]
+    InvalidQuote,
+
+    #[error("Quote is Expired")]
+    QuoteExpired,
+
+    #[error(
+        "Price moved beyond slippage tolerance. Quoted: {quoted_price}, Current: {current_price}"
+    )]
+    PriceSlippageExceeded {
+        quoted_price: u64,
+        current_price: u64,
+    },
+
+    #[error("Stablecoin {symbol} is depegged from {peg}. Rate: {rate}")]
+    StablecoinDepegged {
+        symbol: String,
+        peg: String,
+        rate: f64,
+    },
+
+    #[error("Trade was already rated")]
+    TradeAlreadyRated,
+
+    #[error("Dispute outcome already recorded for this order")]
+    DisputeAlreadyRecorded,
+
+    #[error("Offramper of the order is suspended or banned")]
+    OfframperRestricted,
+
+    #[error("Order cannot be locked by its own offramper")]
+    OwnOrder,
+
+    #[error("Trade parties are not recorded for this order")]
+    TradeNotRateable,
 }
 
 #[derive(Error, Debug, CandidType, Clone)]
@@ -205,6 +349,9 @@ pub enum SystemError {
     #[error("Exchange rate error: {0:?}")]
     ExchangeRateError(ExchangeRateError),
 
+    #[error("Exchange rate rejected: {0}")]
+    ExchangeRateRejected(String),
+
     #[error("Failed to call exchange rate canister: {0}")]
     CanisterCallError(String),
 
@@ -222,6 +369,9 @@ pub enum SystemError {
 
     #[error("IC Rejection Code: {0:?}, Error: {1}")]
     ICRejectionError(RejectionCode, String),
+
+    #[error("Secret not found: {0}")]
+    SecretNotFound(String),
 }
 
 impl From<ParseFloatError> for SystemError {
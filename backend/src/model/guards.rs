@@ -1,9 +1,29 @@
+use candid::Principal;
+
 use super::errors::{Result, UserError};
+use super::memory::heap::{self, read_state};
+use super::types::access::AccessRole;
+
+pub fn only_role(role: AccessRole) -> Result<()> {
+    if heap::has_role(&ic_cdk::caller(), role) {
+        Ok(())
+    } else {
+        Err(UserError::MissingRole(role).into())
+    }
+}
+
+pub fn caller_not_anonymous() -> Result<Principal> {
+    let caller = ic_cdk::caller();
+    if caller == Principal::anonymous() {
+        return Err(UserError::UnauthorizedPrincipal.into());
+    }
+    Ok(caller)
+}
 
-pub fn only_controller() -> Result<()> {
-    if ic_cdk::api::is_controller(&ic_cdk::caller()) {
+pub fn only_attestor() -> Result<()> {
+    if read_state(|s| s.verification.attestors.contains(&ic_cdk::caller())) {
         Ok(())
     } else {
-        Err(UserError::OnlyController.into())
+        Err(UserError::NotAttestor.into())
     }
 }
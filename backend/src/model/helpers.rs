@@ -7,6 +7,7 @@ use ethers_core::types::{Address, H160};
 
 use crate::{
     errors::{BlockchainError, Result},
+    model::types::btc,
     outcalls::xrc_rates::{self, Asset, AssetClass},
 };
 
@@ -58,7 +59,8 @@ pub fn validate_icp_address(icp_address: &str) -> Result<()> {
     Ok(())
 }
 
-pub fn validate_bitcoin_address(_bitcoin_address: &str) -> Result<()> {
+pub fn validate_bitcoin_address(bitcoin_address: &str) -> Result<()> {
+    btc::parse_address(bitcoin_address, btc::get_network()?)?;
     Ok(())
 }
 
@@ -70,11 +72,20 @@ pub fn validate_email(address: &str) -> Result<()> {
     }
 }
 
-pub fn validate_solana_address(_solana_address: &str) -> Result<()> {
-    // solana_sdk::pubkey::Pubkey::from_str(solana_address).map_err(|_| BlockchainError::InvalidAddress)?;
+pub fn validate_solana_address(solana_address: &str) -> Result<()> {
+    decode_solana_pubkey(solana_address)?;
     Ok(())
 }
 
+/// Solana addresses are base58 encoded ed25519 public keys.
+pub fn decode_solana_pubkey(solana_address: &str) -> Result<[u8; 32]> {
+    bs58::decode(solana_address)
+        .into_vec()
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| BlockchainError::InvalidAddress.into())
+}
+
 pub async fn get_eth_token_rate(token_symbol: String) -> Result<f64> {
     let base_asset = Asset {
         class: AssetClass::Cryptocurrency,
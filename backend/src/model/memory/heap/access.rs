@@ -0,0 +1,57 @@
+use candid::Principal;
+
+use crate::{
+    errors::{Result, UserError},
+    model::types::access::{AccessRole, RoleAssignment},
+};
+
+use super::{mutate_state, read_state};
+
+/// Canister controllers hold every role, so that access can always be recovered.
+pub fn has_role(principal: &Principal, role: AccessRole) -> bool {
+    if ic_cdk::api::is_controller(principal) {
+        return true;
+    }
+
+    read_state(|s| {
+        s.access_roles
+            .get(principal)
+            .is_some_and(|roles| roles.iter().any(|held| held.grants(role)))
+    })
+}
+
+pub fn grant_role(principal: Principal, role: AccessRole) -> Result<()> {
+    if principal == Principal::anonymous() {
+        return Err(UserError::UnauthorizedPrincipal.into());
+    }
+
+    mutate_state(|s| s.access_roles.entry(principal).or_default().insert(role));
+    Ok(())
+}
+
+pub fn revoke_role(principal: &Principal, role: AccessRole) -> Result<()> {
+    mutate_state(|s| {
+        let roles = s
+            .access_roles
+            .get_mut(principal)
+            .filter(|roles| roles.contains(&role))
+            .ok_or(UserError::MissingRole(role))?;
+        roles.remove(&role);
+        if roles.is_empty() {
+            s.access_roles.remove(principal);
+        }
+        Ok(())
+    })
+}
+
+pub fn get_role_assignments() -> Vec<RoleAssignment> {
+    read_state(|s| {
+        s.access_roles
+            .iter()
+            .map(|(principal, roles)| RoleAssignment {
+                principal: *principal,
+                roles: roles.iter().copied().collect(),
+            })
+            .collect()
+    })
+}
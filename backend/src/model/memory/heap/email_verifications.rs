@@ -0,0 +1,23 @@
+use crate::model::types::mail::EmailVerification;
+
+use super::storage::EMAIL_VERIFICATIONS;
+
+/// Keyed by user as well, so that nobody can replace another user's pending verification.
+pub fn store_email_verification(email: &str, user_id: u64, verification: EmailVerification) {
+    EMAIL_VERIFICATIONS.with_borrow_mut(|verifications| {
+        verifications.retain(|_, stored| !stored.is_expired());
+        verifications.insert((email.to_string(), user_id), verification);
+    });
+}
+
+pub fn get_email_verification(email: &str, user_id: u64) -> Option<EmailVerification> {
+    EMAIL_VERIFICATIONS
+        .with_borrow(|verifications| verifications.get(&(email.to_string(), user_id)).cloned())
+}
+
+/// Removes the pending verification once its token was checked, so that every token can be
+/// used a single time.
+pub fn remove_email_verification(email: &str, user_id: u64) {
+    EMAIL_VERIFICATIONS
+        .with_borrow_mut(|verifications| verifications.remove(&(email.to_string(), user_id)));
+}
@@ -1,14 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::{fmt, str::FromStr};
 
-use candid::{CandidType, Deserialize};
+use candid::{CandidType, Deserialize, Principal};
 use evm_rpc_canister_types::RpcServices;
-use ic_cdk::api::management_canister::ecdsa::EcdsaKeyId;
+use ic_cdk::api::management_canister::{bitcoin::BitcoinNetwork, ecdsa::EcdsaKeyId};
 
 use super::state::{InvalidStateError, State};
 use crate::model::types::{
-    evm::chains::ChainState,
+    access::AccessRole,
+    evm::{chains::ChainState, siwe::SiweConfig},
+    exchange_rate::{RatePrefetchConfig, RateQualityConfig, StablecoinRegistry},
+    limits::VerificationConfig,
+    mail::MailConfig,
     payment::{paypal::PayPalState, revolut::RevolutState},
+    rate_limit::RateLimitConfig,
+    secrets::SecretKey,
+    treasury::TreasuryConfig,
 };
 
 #[derive(CandidType, Deserialize, Debug, Clone)]
@@ -19,13 +26,23 @@ pub struct ChainConfig {
     pub currency_symbol: String,
 }
 
-#[derive(CandidType, Deserialize, Debug, Clone)]
+#[derive(CandidType, Deserialize, Clone)]
 pub struct PaypalConfig {
     pub client_id: String,
     pub client_secret: String,
     pub api_url: String,
 }
 
+impl fmt::Debug for PaypalConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PaypalConfig")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"[REDACTED]")
+            .field("api_url", &self.api_url)
+            .finish()
+    }
+}
+
 #[derive(CandidType, Deserialize, Clone)]
 pub struct RevolutConfig {
     pub client_id: String,
@@ -56,6 +73,37 @@ pub struct InitArg {
     pub paypal: PaypalConfig,
     pub revolut: RevolutConfig,
     pub proxy_url: String,
+    pub rate_quality: Option<RateQualityConfig>,
+    pub rate_prefetch: Option<RatePrefetchConfig>,
+    pub stablecoins: Option<StablecoinRegistry>,
+    pub siwe: Option<SiweConfig>,
+    pub bitcoin_network: BitcoinNetwork,
+    pub mail: Option<MailConfig>,
+    pub verification: Option<VerificationConfig>,
+    pub rate_limits: Option<RateLimitConfig>,
+    pub access_roles: Option<HashMap<Principal, HashSet<AccessRole>>>,
+    pub treasury: Option<TreasuryConfig>,
+}
+
+impl InitArg {
+    /// Takes the credentials out of the arguments, to be kept in the secret store rather than
+    /// in the state.
+    pub fn take_secrets(&mut self) -> Vec<(SecretKey, Vec<u8>)> {
+        let mut secrets = vec![
+            (
+                SecretKey::PaypalClientSecret,
+                std::mem::take(&mut self.paypal.client_secret).into_bytes(),
+            ),
+            (
+                SecretKey::RevolutPrivateKey,
+                std::mem::take(&mut self.revolut.private_key_der),
+            ),
+        ];
+        if let Some(api_key) = self.mail.as_mut().and_then(MailConfig::take_api_key) {
+            secrets.push((SecretKey::MailApiKey, api_key.into_bytes()));
+        }
+        secrets
+    }
 }
 
 impl TryFrom<InitArg> for State {
@@ -68,6 +116,16 @@ impl TryFrom<InitArg> for State {
             paypal,
             revolut,
             proxy_url,
+            rate_quality,
+            rate_prefetch,
+            stablecoins,
+            siwe,
+            bitcoin_network,
+            mail,
+            verification,
+            rate_limits,
+            access_roles,
+            treasury,
         }: InitArg,
     ) -> Result<Self, Self::Error> {
         let mut chains_map = HashMap::new();
@@ -95,7 +153,7 @@ impl TryFrom<InitArg> for State {
                 access_token: None,
                 token_expiration: None,
                 client_id: paypal.client_id,
-                client_secret: paypal.client_secret,
+                client_secret: None,
                 api_url: paypal.api_url,
             },
             revolut: RevolutState {
@@ -104,12 +162,22 @@ impl TryFrom<InitArg> for State {
                 client_id: revolut.client_id,
                 api_url: revolut.api_url,
                 proxy_url: revolut.proxy_url,
-                private_key_der: revolut.private_key_der,
+                private_key_der: None,
                 kid: revolut.kid,
                 tan: revolut.tan,
             },
             proxy_url,
             icp_tokens: HashMap::new(),
+            rate_quality: rate_quality.unwrap_or_default(),
+            rate_prefetch: rate_prefetch.unwrap_or_default(),
+            stablecoins: stablecoins.unwrap_or_default(),
+            siwe: siwe.unwrap_or_default(),
+            bitcoin_network: Some(bitcoin_network),
+            mail: mail.unwrap_or_default(),
+            verification: verification.unwrap_or_default(),
+            rate_limits: rate_limits.unwrap_or_default(),
+            access_roles: access_roles.unwrap_or_default(),
+            treasury: treasury.unwrap_or_default(),
         };
         Ok(state)
     }
@@ -1,12 +1,22 @@
+mod access;
+mod email_verifications;
 mod init;
 pub mod logs;
+mod password_resets;
+mod quotes;
 mod rate;
+mod rate_limits;
 mod state;
 mod storage;
 pub mod upgrade;
 
+pub use access::*;
+pub use email_verifications::*;
 pub use init::InitArg;
+pub use password_resets::*;
+pub use quotes::*;
 pub use rate::*;
+pub use rate_limits::*;
 pub use state::*;
 pub use storage::*;
 pub use upgrade::UpdateArg;
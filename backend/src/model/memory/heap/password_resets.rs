@@ -0,0 +1,20 @@
+use crate::model::types::mail::PasswordReset;
+
+use super::storage::PASSWORD_RESETS;
+
+pub fn store_password_reset(email: &str, reset: PasswordReset) {
+    PASSWORD_RESETS.with_borrow_mut(|resets| {
+        resets.retain(|_, stored| !stored.is_expired());
+        resets.insert(email.to_string(), reset);
+    });
+}
+
+pub fn get_password_reset(email: &str) -> Option<PasswordReset> {
+    PASSWORD_RESETS.with_borrow(|resets| resets.get(email).cloned())
+}
+
+/// Removes the pending reset once its token was verified, so that every token can be used
+/// a single time.
+pub fn remove_password_reset(email: &str) {
+    PASSWORD_RESETS.with_borrow_mut(|resets| resets.remove(email));
+}
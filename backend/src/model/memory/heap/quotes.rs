@@ -0,0 +1,20 @@
+use crate::model::types::orders::OrderQuote;
+
+use super::storage::ORDER_QUOTES;
+
+pub fn store_quote(quote: OrderQuote) {
+    ORDER_QUOTES.with_borrow_mut(|quotes| {
+        quotes.retain(|_, stored| !stored.is_expired());
+        quotes.insert(quote.id.clone(), quote);
+    });
+}
+
+pub fn get_quote(quote_id: &str) -> Option<OrderQuote> {
+    ORDER_QUOTES.with_borrow(|quotes| quotes.get(quote_id).cloned())
+}
+
+/// Removes the quote once the lock went through, so that every quote can be used
+/// for a single lock.
+pub fn remove_quote(quote_id: &str) {
+    ORDER_QUOTES.with_borrow_mut(|quotes| quotes.remove(quote_id));
+}
@@ -1,6 +1,16 @@
-use crate::{model::types::exchange_rate::ExchangeRateCache, outcalls::xrc_rates::Asset};
+use std::collections::HashSet;
 
-use super::storage::EXCHANGE_RATE_CACHE;
+use crate::{
+    model::types::{
+        exchange_rate::{ExchangeRateCache, RatePrefetchStatus, RejectedRate},
+        orders::OrderState,
+    },
+    outcalls::xrc_rates::Asset,
+};
+
+use super::storage::{
+    ACTIVE_TRADING_PAIRS, EXCHANGE_RATE_CACHE, RATE_PREFETCH_STATUS, REJECTED_RATES,
+};
 
 pub fn cache_exchange_rate(base_asset: Asset, quote_asset: Asset, rate: f64) {
     EXCHANGE_RATE_CACHE.with_borrow_mut(|rates| {
@@ -18,3 +28,70 @@ pub fn get_cached_rate(base_asset: Asset, quote_asset: Asset) -> Option<f64> {
             .and_then(|rate| rate.get_cached_rate())
     })
 }
+
+pub fn rate_expires_within(base_asset: &Asset, quote_asset: &Asset, margin: u64) -> bool {
+    EXCHANGE_RATE_CACHE.with_borrow(|rates| {
+        rates
+            .get(&(base_asset.symbol.clone(), quote_asset.symbol.clone()))
+            .is_none_or(|rate| rate.expires_within(margin))
+    })
+}
+
+pub fn register_rejected_rate(rejected_rate: RejectedRate) {
+    REJECTED_RATES.with_borrow_mut(|rejected| {
+        rejected.insert(
+            (
+                rejected_rate.base_symbol.clone(),
+                rejected_rate.quote_symbol.clone(),
+            ),
+            rejected_rate,
+        )
+    });
+}
+
+pub fn clear_rejected_rate(base_symbol: &str, quote_symbol: &str) {
+    REJECTED_RATES.with_borrow_mut(|rejected| {
+        rejected.remove(&(base_symbol.to_string(), quote_symbol.to_string()))
+    });
+}
+
+pub fn get_rejected_rates() -> Vec<RejectedRate> {
+    REJECTED_RATES.with_borrow(|rejected| rejected.values().cloned().collect())
+}
+
+pub fn mutate_rate_prefetch_status<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut RatePrefetchStatus) -> R,
+{
+    RATE_PREFETCH_STATUS.with_borrow_mut(f)
+}
+
+pub fn get_rate_prefetch_status() -> RatePrefetchStatus {
+    RATE_PREFETCH_STATUS.with_borrow(|status| status.clone())
+}
+
+/// Keeps the trading pair of an order indexed for as long as it is created or locked,
+/// so that the rate prefetcher does not have to walk the order history.
+pub fn track_trading_pair(order_id: u64, order_state: &OrderState) {
+    let order = match order_state {
+        OrderState::Created(order) => Some(order),
+        OrderState::Locked(order) => Some(&order.base),
+        _ => None,
+    };
+    let pair = order.and_then(|order| {
+        order
+            .crypto
+            .get_symbol()
+            .ok()
+            .map(|symbol| (symbol, order.currency.clone()))
+    });
+
+    ACTIVE_TRADING_PAIRS.with_borrow_mut(|pairs| match pair {
+        Some(pair) => pairs.insert(order_id, pair),
+        None => pairs.remove(&order_id),
+    });
+}
+
+pub fn get_active_trading_pairs() -> HashSet<(String, String)> {
+    ACTIVE_TRADING_PAIRS.with_borrow(|pairs| pairs.values().cloned().collect())
+}
@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use candid::Principal;
+
+use crate::{
+    errors::{Result, UserError},
+    model::types::rate_limit::{RateLimitClass, RateLimitEntry, RateLimitKey, TokenBucket},
+};
+
+use super::{read_state, storage::RATE_LIMIT_BUCKETS};
+
+const MAX_RATE_LIMIT_BUCKETS: usize = 50_000;
+const PRUNE_INTERVAL_SECS: u64 = 60;
+
+/// Takes a call from the bucket of the caller for `class`. Anonymous callers share a single
+/// principal, and so a single bucket.
+pub fn check_rate_limit(class: RateLimitClass) -> Result<()> {
+    let caller = ic_cdk::caller();
+    let key = if caller == Principal::anonymous() {
+        RateLimitKey::Anonymous
+    } else {
+        RateLimitKey::Principal(caller)
+    };
+    take_call(class, key)
+}
+
+/// Takes a call from the bucket of `user_id` for `class`. Only call it once the caller has
+/// proven to act for the user, so that nobody else can drain the user's bucket.
+pub fn check_user_rate_limit(class: RateLimitClass, user_id: u64) -> Result<()> {
+    take_call(class, RateLimitKey::User(user_id))
+}
+
+fn take_call(class: RateLimitClass, key: RateLimitKey) -> Result<()> {
+    let rate_limits = read_state(|s| s.rate_limits.clone());
+    let Some(config) = rate_limits.limits.get(&class).copied() else {
+        return Ok(());
+    };
+    if !rate_limits.enabled {
+        return Ok(());
+    }
+
+    let now = ic_cdk::api::time();
+    RATE_LIMIT_BUCKETS.with_borrow_mut(|buckets| {
+        // once the map is full, callers without a bucket share the anonymous one
+        let key = if buckets.len() >= MAX_RATE_LIMIT_BUCKETS
+            && !buckets.contains_key(&(key.clone(), class))
+        {
+            RateLimitKey::Anonymous
+        } else {
+            key
+        };
+        let config = config.for_key(&key);
+        let mut bucket = buckets
+            .get(&(key.clone(), class))
+            .cloned()
+            .unwrap_or_else(|| TokenBucket::new(&config, now));
+        bucket
+            .try_take(&config, now)
+            .map_err(|retry_after_secs| UserError::RateLimited { retry_after_secs })?;
+        buckets.insert((key, class), bucket);
+        Ok(())
+    })
+}
+
+pub fn setup_rate_limit_pruning_timer() {
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(PRUNE_INTERVAL_SECS), || {
+        prune_rate_limits(ic_cdk::api::time())
+    });
+}
+
+/// Drops the buckets that are full again, as they behave like new ones.
+fn prune_rate_limits(now: u64) {
+    let rate_limits = read_state(|s| s.rate_limits.clone());
+    RATE_LIMIT_BUCKETS.with_borrow_mut(|buckets| {
+        buckets.retain(|(key, class), bucket| {
+            rate_limits
+                .limits
+                .get(class)
+                .is_some_and(|config| !bucket.is_full(&config.for_key(key), now))
+        })
+    });
+}
+
+pub fn get_rate_limit_entries() -> Vec<RateLimitEntry> {
+    RATE_LIMIT_BUCKETS.with_borrow(|buckets| {
+        buckets
+            .iter()
+            .map(|((key, class), bucket)| RateLimitEntry {
+                key: key.clone(),
+                class: *class,
+                bucket: bucket.clone(),
+            })
+            .collect()
+    })
+}
+
+/// Resets the buckets of `key`, or all buckets when no key is given.
+pub fn reset_rate_limits(key: Option<RateLimitKey>) {
+    RATE_LIMIT_BUCKETS.with_borrow_mut(|buckets| match key {
+        Some(key) => buckets.retain(|(bucket_key, _), _| *bucket_key != key),
+        None => buckets.clear(),
+    });
+}
@@ -1,12 +1,18 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use candid::{CandidType, Deserialize, Principal};
-use ic_cdk::api::management_canister::ecdsa::EcdsaKeyId;
+use ic_cdk::api::management_canister::{bitcoin::BitcoinNetwork, ecdsa::EcdsaKeyId};
 
 use crate::model::types::{
-    evm::chains::ChainState,
+    access::AccessRole,
+    evm::{chains::ChainState, siwe::SiweConfig},
+    exchange_rate::{RatePrefetchConfig, RateQualityConfig, StablecoinRegistry},
     icp::IcpToken,
+    limits::VerificationConfig,
+    mail::MailConfig,
     payment::{paypal::PayPalState, revolut::RevolutState},
+    rate_limit::RateLimitConfig,
+    treasury::TreasuryConfig,
 };
 
 use super::storage::STATE;
@@ -21,6 +27,16 @@ pub struct State {
     pub revolut: RevolutState,
     pub proxy_url: String,
     pub icp_tokens: HashMap<Principal, IcpToken>,
+    pub rate_quality: RateQualityConfig,
+    pub rate_prefetch: RatePrefetchConfig,
+    pub stablecoins: StablecoinRegistry,
+    pub siwe: SiweConfig,
+    pub bitcoin_network: Option<BitcoinNetwork>, // only unset in states saved before Bitcoin logins
+    pub mail: MailConfig,
+    pub verification: VerificationConfig,
+    pub rate_limits: RateLimitConfig,
+    pub access_roles: HashMap<Principal, HashSet<AccessRole>>,
+    pub treasury: TreasuryConfig,
 }
 
 #[derive(Debug, Eq, PartialEq)]
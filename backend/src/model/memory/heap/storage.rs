@@ -5,7 +5,13 @@ use super::State;
 use crate::{
     errors::{OrderError, Result},
     management,
-    types::{evm::logs::EvmTransactionLog, exchange_rate::ExchangeRateCache},
+    types::{
+        evm::logs::EvmTransactionLog,
+        exchange_rate::{ExchangeRateCache, RatePrefetchStatus, RejectedRate},
+        mail::{EmailVerification, PasswordReset},
+        orders::OrderQuote,
+        rate_limit::{RateLimitClass, RateLimitKey, TokenBucket},
+    },
 };
 
 pub(crate) const LOCK_DURATION_TIME_SECONDS: u64 = 1800; // 30 min
@@ -20,6 +26,14 @@ thread_local! {
     pub(super) static EVM_TRANSACTION_LOGS: RefCell<HashMap<u64, EvmTransactionLog>> = RefCell::new(HashMap::new());
     pub(super) static TRANSACTION_LOG_TIMERS: RefCell<HashMap<u64, TimerId>> = RefCell::new(HashMap::new());
     pub(super) static EXCHANGE_RATE_CACHE: RefCell<HashMap<(String, String), ExchangeRateCache>> = RefCell::new(HashMap::new());
+    pub(super) static REJECTED_RATES: RefCell<HashMap<(String, String), RejectedRate>> = RefCell::new(HashMap::new());
+    pub(super) static RATE_PREFETCH_STATUS: RefCell<RatePrefetchStatus> = RefCell::default();
+    // order id -> (crypto symbol, fiat currency) of created and locked orders
+    pub(super) static ACTIVE_TRADING_PAIRS: RefCell<HashMap<u64, (String, String)>> = RefCell::new(HashMap::new());
+    pub(super) static ORDER_QUOTES: RefCell<HashMap<String, OrderQuote>> = RefCell::new(HashMap::new());
+    pub(super) static PASSWORD_RESETS: RefCell<HashMap<String, PasswordReset>> = RefCell::new(HashMap::new());
+    pub(super) static EMAIL_VERIFICATIONS: RefCell<HashMap<(String, u64), EmailVerification>> = RefCell::new(HashMap::new());
+    pub(super) static RATE_LIMIT_BUCKETS: RefCell<HashMap<(RateLimitKey, RateLimitClass), TokenBucket>> = RefCell::new(HashMap::new());
 }
 
 pub fn tmp_get_rate() -> HashMap<(String, String), ExchangeRateCache> {
@@ -1,18 +1,30 @@
-use std::{borrow::Cow, collections::HashMap};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
 
-use candid::{CandidType, Decode, Deserialize, Encode};
-use ic_cdk::api::management_canister::ecdsa::EcdsaKeyId;
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_cdk::api::management_canister::{bitcoin::BitcoinNetwork, ecdsa::EcdsaKeyId};
 use ic_cdk_timers::TimerId;
 use ic_stable_structures::{storable::Bound, Storable};
 
 use crate::{
     management,
     model::{
-        memory::stable::storage::HEAP_STATE,
+        memory::stable::{secrets, storage::HEAP_STATE},
         types::{
-            evm::chains::ChainState,
-            exchange_rate::ExchangeRateCache,
+            access::AccessRole,
+            evm::{chains::ChainState, siwe::SiweConfig},
+            exchange_rate::{
+                ExchangeRateCache, RatePrefetchConfig, RateQualityConfig, StablecoinRegistry,
+            },
+            icp::IcpToken,
+            limits::VerificationConfig,
+            mail::MailConfig,
             payment::{paypal::PayPalState, revolut::RevolutState},
+            rate_limit::RateLimitConfig,
+            secrets::SecretKey,
+            treasury::TreasuryConfig,
         },
     },
 };
@@ -34,15 +46,24 @@ pub struct UpdateArg {
     pub paypal: Option<PaypalConfig>,     // Optional PayPal configuration update
     pub revolut: Option<RevolutConfig>,   // Optional Revolut configuration update
     pub proxy_url: Option<String>,        // Optional proxy URL update
+    pub rate_quality: Option<RateQualityConfig>, // Optional XRC rate quality update
+    pub rate_prefetch: Option<RatePrefetchConfig>, // Optional rate prefetching update
+    pub stablecoins: Option<StablecoinRegistry>, // Optional stablecoin registry update
+    pub siwe: Option<SiweConfig>,         // Optional Sign-In with Ethereum update
+    pub bitcoin_network: Option<BitcoinNetwork>, // Optional Bitcoin network update
+    pub mail: Option<MailConfig>,         // Optional mail sender update
+    pub verification: Option<VerificationConfig>, // Optional verification tiers and limits update
+    pub rate_limits: Option<RateLimitConfig>, // Optional rate limiter update
+    pub treasury: Option<TreasuryConfig>, // Optional treasury signer set update
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub struct SerializableHeap {
-    user_id_counter: u64,
-    order_id_counter: u64,
+    pub(crate) user_id_counter: u64,
+    pub(crate) order_id_counter: u64,
     locked_order_timers: HashMap<u64, u64>,
     exchange_rate_cache: HashMap<(String, String), ExchangeRateCache>,
-    state: State,
+    pub(crate) state: State,
 }
 
 impl Storable for SerializableHeap {
@@ -51,7 +72,9 @@ impl Storable for SerializableHeap {
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        Decode!(bytes.as_ref(), Self)
+            .or_else(|_| Decode!(bytes.as_ref(), LegacySerializableHeap).map(Self::from))
+            .unwrap()
     }
 
     const BOUND: Bound = Bound::Bounded {
@@ -60,6 +83,71 @@ impl Storable for SerializableHeap {
     };
 }
 
+/// Earlier layouts of the saved heap. Settings added to the state since the first
+/// release are optional so that any past version decodes into this struct.
+#[derive(CandidType, Deserialize)]
+struct LegacySerializableHeap {
+    user_id_counter: u64,
+    order_id_counter: u64,
+    locked_order_timers: HashMap<u64, u64>,
+    exchange_rate_cache: HashMap<(String, String), ExchangeRateCache>,
+    state: LegacyState,
+}
+
+#[derive(CandidType, Deserialize)]
+struct LegacyState {
+    chains: HashMap<u64, ChainState>,
+    ecdsa_pub_key: Option<Vec<u8>>,
+    ecdsa_key_id: EcdsaKeyId,
+    evm_address: Option<String>,
+    paypal: PayPalState,
+    revolut: RevolutState,
+    proxy_url: String,
+    icp_tokens: HashMap<Principal, IcpToken>,
+    rate_quality: Option<RateQualityConfig>,
+    rate_prefetch: Option<RatePrefetchConfig>,
+    stablecoins: Option<StablecoinRegistry>,
+    siwe: Option<SiweConfig>,
+    bitcoin_network: Option<BitcoinNetwork>,
+    mail: Option<MailConfig>,
+    verification: Option<VerificationConfig>,
+    rate_limits: Option<RateLimitConfig>,
+    access_roles: Option<HashMap<Principal, HashSet<AccessRole>>>,
+    treasury: Option<TreasuryConfig>,
+}
+
+impl From<LegacySerializableHeap> for SerializableHeap {
+    fn from(legacy: LegacySerializableHeap) -> Self {
+        let state = legacy.state;
+        SerializableHeap {
+            user_id_counter: legacy.user_id_counter,
+            order_id_counter: legacy.order_id_counter,
+            locked_order_timers: legacy.locked_order_timers,
+            exchange_rate_cache: legacy.exchange_rate_cache,
+            state: State {
+                chains: state.chains,
+                ecdsa_pub_key: state.ecdsa_pub_key,
+                ecdsa_key_id: state.ecdsa_key_id,
+                evm_address: state.evm_address,
+                paypal: state.paypal,
+                revolut: state.revolut,
+                proxy_url: state.proxy_url,
+                icp_tokens: state.icp_tokens,
+                rate_quality: state.rate_quality.unwrap_or_default(),
+                rate_prefetch: state.rate_prefetch.unwrap_or_default(),
+                stablecoins: state.stablecoins.unwrap_or_default(),
+                siwe: state.siwe.unwrap_or_default(),
+                bitcoin_network: state.bitcoin_network,
+                mail: state.mail.unwrap_or_default(),
+                verification: state.verification.unwrap_or_default(),
+                rate_limits: state.rate_limits.unwrap_or_default(),
+                access_roles: state.access_roles.unwrap_or_default(),
+                treasury: state.treasury.unwrap_or_default(),
+            },
+        }
+    }
+}
+
 impl SerializableHeap {
     pub fn from_internal(
         user_id_counter: u64,
@@ -125,9 +213,17 @@ pub fn post_upgrade(update_arg: Option<UpdateArg>) {
             set_exchange_rate_cache(serializable_heap.exchange_rate_cache);
 
             let mut state: State = serializable_heap.state.clone();
+            for (key, value) in take_legacy_secrets(&mut state) {
+                if !secrets::contains_secret(key) {
+                    secrets::rotate_secret(key, value);
+                }
+            }
             if let Some(update_arg) = update_arg {
                 update_state(update_arg, &mut state);
             }
+            if state.bitcoin_network.is_none() {
+                ic_cdk::trap("bitcoin_network must be set when upgrading from a state without it");
+            }
 
             initialize_state(state);
         } else {
@@ -136,6 +232,27 @@ pub fn post_upgrade(update_arg: Option<UpdateArg>) {
     });
 }
 
+/// Takes the credentials out of states saved before the secret store, to be moved into it.
+pub(crate) fn take_legacy_secrets(state: &mut State) -> Vec<(SecretKey, Vec<u8>)> {
+    [
+        (
+            SecretKey::PaypalClientSecret,
+            state.paypal.client_secret.take().map(String::into_bytes),
+        ),
+        (
+            SecretKey::RevolutPrivateKey,
+            state.revolut.private_key_der.take(),
+        ),
+        (
+            SecretKey::MailApiKey,
+            state.mail.take_api_key().map(String::into_bytes),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key, value?)))
+    .collect()
+}
+
 fn update_state(update_arg: UpdateArg, state: &mut State) {
     // Update or add chains
     if let Some(chains) = update_arg.chains {
@@ -163,23 +280,28 @@ fn update_state(update_arg: UpdateArg, state: &mut State) {
 
     // Update PayPal
     if let Some(paypal_config) = update_arg.paypal {
+        secrets::rotate_secret(
+            SecretKey::PaypalClientSecret,
+            paypal_config.client_secret.into_bytes(),
+        );
         state.paypal = PayPalState {
             access_token: None, // Reset access token on upgrade
             token_expiration: None,
             client_id: paypal_config.client_id,
-            client_secret: paypal_config.client_secret,
+            client_secret: None,
             api_url: paypal_config.api_url,
         };
     }
 
     if let Some(revolut_config) = update_arg.revolut {
+        secrets::rotate_secret(SecretKey::RevolutPrivateKey, revolut_config.private_key_der);
         state.revolut = RevolutState {
             access_token: None, // Reset access token on update
             token_expiration: None,
             client_id: revolut_config.client_id,
             api_url: revolut_config.api_url,
             proxy_url: revolut_config.proxy_url,
-            private_key_der: revolut_config.private_key_der,
+            private_key_der: None,
             kid: revolut_config.kid,
             tan: revolut_config.tan,
         };
@@ -188,4 +310,43 @@ fn update_state(update_arg: UpdateArg, state: &mut State) {
     if let Some(proxy_url) = update_arg.proxy_url {
         state.proxy_url = proxy_url;
     }
+
+    if let Some(rate_quality) = update_arg.rate_quality {
+        state.rate_quality = rate_quality;
+    }
+
+    if let Some(rate_prefetch) = update_arg.rate_prefetch {
+        state.rate_prefetch = rate_prefetch;
+    }
+
+    if let Some(stablecoins) = update_arg.stablecoins {
+        state.stablecoins = stablecoins;
+    }
+
+    if let Some(siwe) = update_arg.siwe {
+        state.siwe = siwe;
+    }
+
+    if let Some(bitcoin_network) = update_arg.bitcoin_network {
+        state.bitcoin_network = Some(bitcoin_network);
+    }
+
+    if let Some(mut mail) = update_arg.mail {
+        if let Some(api_key) = mail.take_api_key() {
+            secrets::rotate_secret(SecretKey::MailApiKey, api_key.into_bytes());
+        }
+        state.mail = mail;
+    }
+
+    if let Some(verification) = update_arg.verification {
+        state.verification = verification;
+    }
+
+    if let Some(rate_limits) = update_arg.rate_limits {
+        state.rate_limits = rate_limits;
+    }
+
+    if let Some(treasury) = update_arg.treasury {
+        state.treasury = treasury;
+    }
 }
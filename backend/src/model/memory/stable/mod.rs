@@ -1,4 +1,10 @@
+pub mod moderation;
 pub mod orders;
+pub mod reputation;
+pub mod secrets;
+pub mod sessions;
 pub mod spent_transactions;
 pub mod storage;
+pub mod treasury;
 pub mod users;
+pub mod volumes;
@@ -0,0 +1,55 @@
+use crate::errors::{Result, UserError};
+use crate::types::moderation::{Appeal, AppealStatus, AuditEntry};
+
+use super::storage::{APPEALS, MODERATION_AUDIT};
+
+pub fn add_audit_entry(entry: AuditEntry) {
+    MODERATION_AUDIT.with_borrow_mut(|audit| {
+        let id = audit.last_key_value().map(|(id, _)| id + 1).unwrap_or(0);
+        audit.insert(id, entry);
+    });
+}
+
+pub fn get_audit_entries(user_id: Option<u64>) -> Vec<AuditEntry> {
+    MODERATION_AUDIT.with_borrow(|audit| {
+        audit
+            .iter()
+            .map(|(_, entry)| entry)
+            .filter(|entry| user_id.is_none_or(|user_id| entry.user_id == user_id))
+            .collect()
+    })
+}
+
+/// Stores the appeal, replacing a reviewed one. Only one appeal per user can be pending.
+pub fn submit_appeal(appeal: Appeal) -> Result<()> {
+    APPEALS.with_borrow_mut(|appeals| {
+        if appeals
+            .get(&appeal.user_id)
+            .is_some_and(|existing| existing.status == AppealStatus::Pending)
+        {
+            return Err(UserError::AppealAlreadyPending.into());
+        }
+        appeals.insert(appeal.user_id, appeal);
+        Ok(())
+    })
+}
+
+pub fn get_pending_appeals() -> Vec<Appeal> {
+    APPEALS.with_borrow(|appeals| {
+        appeals
+            .iter()
+            .map(|(_, appeal)| appeal)
+            .filter(|appeal| appeal.status == AppealStatus::Pending)
+            .collect()
+    })
+}
+
+pub fn get_appeal(user_id: u64) -> Result<Appeal> {
+    APPEALS
+        .with_borrow(|appeals| appeals.get(&user_id))
+        .ok_or_else(|| UserError::AppealNotFound.into())
+}
+
+pub fn insert_appeal(appeal: Appeal) {
+    APPEALS.with_borrow_mut(|appeals| appeals.insert(appeal.user_id, appeal));
+}
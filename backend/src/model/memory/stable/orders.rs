@@ -1,14 +1,51 @@
 use crate::errors::{OrderError, Result};
-use crate::model::memory::heap::{clear_order_timer, set_order_timer};
+use crate::model::memory::heap::{clear_order_timer, set_order_timer, track_trading_pair};
 use crate::types::{
     orders::{Order, OrderState, RevolutConsent},
+    user::UserType,
     PaymentProvider, TransactionAddress,
 };
 
 use super::storage::ORDERS;
 
 pub fn insert_order(order: &Order) -> Option<OrderState> {
-    ORDERS.with_borrow_mut(|p| p.insert(order.id, OrderState::Created(order.clone())))
+    let order_state = OrderState::Created(order.clone());
+    track_trading_pair(order.id, &order_state);
+    ORDERS.with_borrow_mut(|p| p.insert(order.id, order_state))
+}
+
+/// Indexes the trading pairs of open orders, which only live on the heap.
+pub fn rebuild_trading_pair_index() {
+    ORDERS.with_borrow(|orders| {
+        for (id, order_state) in orders.iter() {
+            track_trading_pair(id, &order_state);
+        }
+    });
+}
+
+/// Encrypts the payee details of open orders created before payee encryption, returning
+/// how many were sealed.
+pub fn seal_legacy_payees() -> Result<usize> {
+    let order_ids: Vec<u64> = ORDERS.with_borrow(|orders| {
+        orders
+            .iter()
+            .filter(|(_, order_state)| match order_state {
+                OrderState::Created(order) => order.payee.is_none(),
+                OrderState::Locked(order) => order.base.payee.is_none(),
+                _ => false,
+            })
+            .map(|(id, _)| id)
+            .collect()
+    });
+
+    for order_id in &order_ids {
+        mutate_order(order_id, |order_state| match order_state {
+            OrderState::Created(order) => order.seal_payee(),
+            OrderState::Locked(order) => order.base.seal_payee(),
+            _ => Ok(()),
+        })??;
+    }
+    Ok(order_ids.len())
 }
 
 pub fn get_order(order_id: &u64) -> Result<OrderState> {
@@ -43,6 +80,26 @@ where
     })
 }
 
+/// Whether `user_id` takes part in a created or locked order in `role`.
+pub fn has_open_orders(user_id: u64, role: &UserType) -> bool {
+    ORDERS.with_borrow(|orders| {
+        orders
+            .iter()
+            .any(|(_, order_state)| match (order_state, role) {
+                (OrderState::Created(order), UserType::Offramper) => {
+                    order.offramper_user_id == user_id
+                }
+                (OrderState::Locked(order), UserType::Offramper) => {
+                    order.base.offramper_user_id == user_id
+                }
+                (OrderState::Locked(order), UserType::Onramper) => {
+                    order.onramper.user_id == user_id
+                }
+                _ => false,
+            })
+    })
+}
+
 pub fn mutate_order<F, R>(order_id: &u64, f: F) -> Result<R>
 where
     F: FnOnce(&mut OrderState) -> R,
@@ -50,6 +107,7 @@ where
     ORDERS.with_borrow_mut(|orders| {
         if let Some(mut order_state) = orders.get(order_id) {
             let result = f(&mut order_state);
+            track_trading_pair(*order_id, &order_state);
             orders.insert(*order_id, order_state);
             Ok(result)
         } else {
@@ -100,17 +158,26 @@ pub fn unlock_order(order_id: u64) -> Result<()> {
     mutate_order(&order_id, |order_state| -> Result<()> {
         match order_state {
             OrderState::Locked(order) => {
+                // resealed first, so that a failure leaves the order and the users untouched
+                let mut base_order = order.base.clone();
+                base_order.unset_processing();
+                base_order.reseal_payee()?;
+
                 super::users::mutate_user(order.onramper.user_id, |user| {
-                    user.decrease_score();
+                    user.decrease_score(&UserType::Onramper);
                 })?;
+                super::reputation::mutate_reputation(
+                    order.onramper.user_id,
+                    UserType::Onramper,
+                    |reputation| reputation.record_lock_expiry(),
+                );
+                super::volumes::release_volume(order.onramper.user_id, order_id);
+                super::volumes::release_volume(order.base.offramper_user_id, order_id);
                 ic_cdk::println!(
                     "[unlock_order] score decreased for user #{:?}",
                     order.onramper.user_id
                 );
 
-                let mut base_order = order.base.clone();
-                base_order.unset_processing();
-
                 *order_state = OrderState::Created(base_order);
                 Ok(())
             }
@@ -124,7 +191,8 @@ pub fn unlock_order(order_id: u64) -> Result<()> {
 pub fn cancel_order(order_id: u64) -> Result<()> {
     mutate_order(&order_id, |order_state| -> Result<()> {
         match order_state {
-            OrderState::Created(_) => {
+            OrderState::Created(order) => {
+                super::volumes::release_volume(order.offramper_user_id, order_id);
                 *order_state = OrderState::Cancelled(order_id);
                 Ok(())
             }
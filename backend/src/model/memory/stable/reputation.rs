@@ -0,0 +1,71 @@
+use crate::errors::{OrderError, Result};
+use crate::types::{reputation::Reputation, user::UserType};
+
+use super::storage::{DISPUTE_OUTCOMES, REPUTATIONS, TRADE_RATINGS};
+
+pub fn mutate_reputation<F, R>(user_id: u64, role: UserType, f: F) -> R
+where
+    F: FnOnce(&mut Reputation) -> R,
+{
+    REPUTATIONS.with_borrow_mut(|reputations| {
+        let mut user_reputations = reputations.get(&user_id).unwrap_or_default();
+        let result = f(user_reputations.0.entry(role).or_default());
+        reputations.insert(user_id, user_reputations);
+        result
+    })
+}
+
+pub fn get_reputation(user_id: u64, role: &UserType) -> Reputation {
+    REPUTATIONS.with_borrow(|reputations| {
+        reputations
+            .get(&user_id)
+            .and_then(|user_reputations| user_reputations.0.get(role).cloned())
+            .unwrap_or_default()
+    })
+}
+
+/// Records the rating `rater_id` gave for `order_id`, once per rater and order.
+pub fn add_rating(
+    order_id: u64,
+    rater_id: u64,
+    rated_id: u64,
+    rated_role: UserType,
+    stars: u8,
+) -> Result<()> {
+    TRADE_RATINGS.with_borrow_mut(|ratings| -> Result<()> {
+        if ratings.contains_key(&(order_id, rater_id)) {
+            return Err(OrderError::TradeAlreadyRated.into());
+        }
+        ratings.insert((order_id, rater_id), stars);
+        Ok(())
+    })?;
+
+    mutate_reputation(rated_id, rated_role, |reputation| {
+        reputation.add_rating(stars)
+    });
+    Ok(())
+}
+
+/// Records the outcome of the dispute over `order_id`, once per order.
+pub fn add_dispute_outcome(
+    order_id: u64,
+    onramper_id: u64,
+    offramper_id: u64,
+    onramper_won: bool,
+) -> Result<()> {
+    DISPUTE_OUTCOMES.with_borrow_mut(|outcomes| -> Result<()> {
+        if outcomes.contains_key(&order_id) {
+            return Err(OrderError::DisputeAlreadyRecorded.into());
+        }
+        outcomes.insert(order_id, onramper_won);
+        Ok(())
+    })?;
+
+    mutate_reputation(onramper_id, UserType::Onramper, |r| {
+        r.record_dispute(onramper_won)
+    });
+    mutate_reputation(offramper_id, UserType::Offramper, |r| {
+        r.record_dispute(!onramper_won)
+    });
+    Ok(())
+}
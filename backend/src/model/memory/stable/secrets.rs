@@ -0,0 +1,61 @@
+use crate::errors::{Result, SystemError};
+use crate::types::secrets::{Secret, SecretInfo, SecretKey};
+
+use super::storage::SECRETS;
+
+/// Replaces the value of `key`, bumping its version.
+pub fn rotate_secret(key: SecretKey, value: Vec<u8>) -> SecretInfo {
+    let now = ic_cdk::api::time();
+    SECRETS.with_borrow_mut(|secrets| {
+        let version = secrets
+            .get(&key.as_str().to_string())
+            .map_or(1, |secret| secret.version + 1);
+        let secret = Secret {
+            value,
+            version,
+            rotated_at: now,
+        };
+        secrets.insert(key.as_str().to_string(), secret);
+
+        SecretInfo {
+            key,
+            version,
+            rotated_at: now,
+        }
+    })
+}
+
+pub fn get_secret(key: SecretKey) -> Result<Vec<u8>> {
+    SECRETS
+        .with_borrow(|secrets| secrets.get(&key.as_str().to_string()))
+        .map(|secret| secret.value)
+        .ok_or_else(|| SystemError::SecretNotFound(key.as_str().to_string()).into())
+}
+
+pub fn get_secret_string(key: SecretKey) -> Result<String> {
+    String::from_utf8(get_secret(key)?).map_err(|_| SystemError::Utf8Error.into())
+}
+
+pub fn contains_secret(key: SecretKey) -> bool {
+    SECRETS.with_borrow(|secrets| secrets.contains_key(&key.as_str().to_string()))
+}
+
+pub fn get_secrets_info() -> Vec<SecretInfo> {
+    [
+        SecretKey::PaypalClientSecret,
+        SecretKey::RevolutPrivateKey,
+        SecretKey::PayeeMasterKey,
+        SecretKey::MailApiKey,
+    ]
+    .into_iter()
+    .filter_map(|key| {
+        SECRETS
+            .with_borrow(|secrets| secrets.get(&key.as_str().to_string()))
+            .map(|secret| SecretInfo {
+                key,
+                version: secret.version,
+                rotated_at: secret.rotated_at,
+            })
+    })
+    .collect()
+}
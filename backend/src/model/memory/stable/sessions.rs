@@ -0,0 +1,66 @@
+use crate::errors::{Result, UserError};
+use crate::types::session::{Session, SessionInfo};
+
+use super::storage::SESSIONS;
+
+pub fn add_session(user_id: u64, session: Session) {
+    SESSIONS.with_borrow_mut(|sessions| {
+        let mut user_sessions = sessions.get(&user_id).unwrap_or_default();
+        user_sessions.add(session);
+        sessions.insert(user_id, user_sessions);
+    });
+}
+
+pub fn validate_session(user_id: u64, token: &str) -> Result<()> {
+    SESSIONS.with_borrow(|sessions| {
+        sessions
+            .get(&user_id)
+            .ok_or(UserError::SessionNotFound)?
+            .find(token)?
+            .validate(token)
+    })
+}
+
+pub fn refresh_session(user_id: u64, token: &str) -> Result<Session> {
+    SESSIONS.with_borrow_mut(|sessions| {
+        let mut user_sessions = sessions.get(&user_id).ok_or(UserError::SessionNotFound)?;
+        let session = user_sessions.find_mut(token)?;
+        session.validate(token)?;
+        session.refresh();
+        let session = session.clone();
+
+        sessions.insert(user_id, user_sessions);
+        Ok(session)
+    })
+}
+
+pub fn list_sessions(user_id: u64) -> Vec<SessionInfo> {
+    SESSIONS.with_borrow(|sessions| {
+        sessions
+            .get(&user_id)
+            .unwrap_or_default()
+            .0
+            .iter()
+            .filter(|session| !session.is_expired())
+            .map(SessionInfo::from)
+            .collect()
+    })
+}
+
+pub fn revoke_session(user_id: u64, session_id: &str) -> Result<()> {
+    SESSIONS.with_borrow_mut(|sessions| {
+        let mut user_sessions = sessions.get(&user_id).ok_or(UserError::SessionNotFound)?;
+        let count = user_sessions.0.len();
+        user_sessions.0.retain(|session| session.id != session_id);
+        if user_sessions.0.len() == count {
+            return Err(UserError::SessionNotFound.into());
+        }
+
+        sessions.insert(user_id, user_sessions);
+        Ok(())
+    })
+}
+
+pub fn revoke_all_sessions(user_id: u64) {
+    SESSIONS.with_borrow_mut(|sessions| sessions.remove(&user_id));
+}
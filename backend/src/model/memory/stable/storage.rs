@@ -4,7 +4,13 @@ use std::cell::RefCell;
 
 use crate::model::memory::heap::upgrade::SerializableHeap;
 use crate::types::{
+    limits::FiatVolume,
+    moderation::{Appeal, AuditEntry},
     orders::{OrderId, OrderState},
+    reputation::UserReputations,
+    secrets::Secret,
+    session::UserSessions,
+    treasury::TreasuryProposal,
     user::User,
 };
 
@@ -37,4 +43,70 @@ thread_local! {
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3)))
         )
     );
+
+    pub static SESSIONS: RefCell<StableBTreeMap<u64, UserSessions, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4)))
+        )
+    );
+
+    // normalised login address -> user id
+    pub static LOGIN_INDEX: RefCell<StableBTreeMap<String, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5)))
+        )
+    );
+
+    // user id -> fiat volume of the last month
+    pub static USER_VOLUMES: RefCell<StableBTreeMap<u64, FiatVolume, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6)))
+        )
+    );
+
+    pub static REPUTATIONS: RefCell<StableBTreeMap<u64, UserReputations, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7)))
+        )
+    );
+
+    // (order id, rater user id) -> stars
+    pub static TRADE_RATINGS: RefCell<StableBTreeMap<(u64, u64), u8, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8)))
+        )
+    );
+
+    pub static MODERATION_AUDIT: RefCell<StableBTreeMap<u64, AuditEntry, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9)))
+        )
+    );
+
+    // user id -> latest appeal
+    pub static APPEALS: RefCell<StableBTreeMap<u64, Appeal, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10)))
+        )
+    );
+
+    pub static TREASURY_PROPOSALS: RefCell<StableBTreeMap<u64, TreasuryProposal, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11)))
+        )
+    );
+
+    // secret key name -> secret, kept out of the heap state so it is never logged
+    pub static SECRETS: RefCell<StableBTreeMap<String, Secret, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12)))
+        )
+    );
+
+    // order id -> whether the onramper won the dispute
+    pub static DISPUTE_OUTCOMES: RefCell<StableBTreeMap<u64, bool, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13)))
+        )
+    );
 }
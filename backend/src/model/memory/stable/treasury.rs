@@ -0,0 +1,55 @@
+use crate::errors::{Result, UserError};
+use crate::types::treasury::TreasuryProposal;
+
+use super::storage::TREASURY_PROPOSALS;
+
+pub fn next_proposal_id() -> u64 {
+    TREASURY_PROPOSALS.with_borrow(|proposals| {
+        proposals
+            .last_key_value()
+            .map(|(id, _)| id + 1)
+            .unwrap_or(0)
+    })
+}
+
+pub fn insert_proposal(proposal: TreasuryProposal) {
+    TREASURY_PROPOSALS.with_borrow_mut(|proposals| proposals.insert(proposal.id, proposal));
+}
+
+pub fn get_proposal(proposal_id: u64) -> Result<TreasuryProposal> {
+    TREASURY_PROPOSALS
+        .with_borrow(|proposals| proposals.get(&proposal_id))
+        .ok_or_else(|| UserError::ProposalNotFound.into())
+}
+
+pub fn mutate_proposal<F, R>(proposal_id: u64, f: F) -> Result<R>
+where
+    F: FnOnce(&mut TreasuryProposal) -> Result<R>,
+{
+    TREASURY_PROPOSALS.with_borrow_mut(|proposals| {
+        let mut proposal = proposals
+            .get(&proposal_id)
+            .ok_or(UserError::ProposalNotFound)?;
+        let result = f(&mut proposal)?;
+        proposals.insert(proposal_id, proposal);
+        Ok(result)
+    })
+}
+
+/// Proposals newest first.
+pub fn get_proposals(page: Option<u32>, page_size: Option<u32>) -> Vec<TreasuryProposal> {
+    let page_size = page_size.unwrap_or(10);
+    let start_index = page.unwrap_or(1).saturating_sub(1) * page_size;
+
+    TREASURY_PROPOSALS.with_borrow(|proposals| {
+        proposals
+            .iter()
+            .map(|(_, proposal)| proposal)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .skip(start_index as usize)
+            .take(page_size as usize)
+            .collect()
+    })
+}
@@ -1,7 +1,11 @@
+use std::collections::HashSet;
+
+use candid::Principal;
+
 use crate::errors::{Result, UserError};
 use crate::types::{user::User, LoginAddress};
 
-use super::storage::USERS;
+use super::storage::{LOGIN_INDEX, USERS};
 
 pub fn mutate_user<F, R>(user_id: u64, f: F) -> Result<R>
 where
@@ -9,7 +13,9 @@ where
 {
     USERS.with_borrow_mut(|users| {
         if let Some(mut user) = users.get(&user_id) {
+            let previous_logins = user.logins.clone();
             let result = f(&mut user);
+            update_login_index(user_id, &previous_logins, &user.logins)?;
             users.insert(user_id, user);
             Ok(result)
         } else {
@@ -18,14 +24,24 @@ where
     })
 }
 
-pub fn insert_user(user: &User) -> Option<User> {
-    USERS.with_borrow_mut(|p| p.insert(user.id, user.clone()))
+/// Inserts the user, failing if one of its logins belongs to another user.
+pub fn insert_user(user: &User) -> Result<Option<User>> {
+    USERS.with_borrow_mut(|users| {
+        let previous_logins = users
+            .get(&user.id)
+            .map(|previous| previous.logins)
+            .unwrap_or_default();
+        update_login_index(user.id, &previous_logins, &user.logins)?;
+        Ok(users.insert(user.id, user.clone()))
+    })
 }
 
 pub fn remove_user(user_id: &u64) -> Result<User> {
-    USERS
+    let user = USERS
         .with_borrow_mut(|p| p.remove(user_id))
-        .ok_or_else(|| UserError::UserNotFound.into())
+        .ok_or(UserError::UserNotFound)?;
+    update_login_index(*user_id, &user.logins, &HashSet::new())?;
+    Ok(user)
 }
 
 pub fn get_user(user_id: &u64) -> Result<User> {
@@ -35,35 +51,70 @@ pub fn get_user(user_id: &u64) -> Result<User> {
 }
 
 pub fn find_user_by_login_address(login_address: &LoginAddress) -> Result<u64> {
-    USERS.with(|users| {
-        for (id, user) in users.borrow().iter() {
-            if user.login == *login_address {
-                return Ok(id);
-            }
-        }
-        Err(UserError::UserNotFound.into())
-    })
+    LOGIN_INDEX
+        .with_borrow(|index| index.get(&login_address.index_key()))
+        .ok_or_else(|| UserError::UserNotFound.into())
+}
+
+pub fn find_user_by_principal(principal: &Principal) -> Result<User> {
+    let login_address = LoginAddress::ICP {
+        principal_id: principal.to_text(),
+    };
+    let user = get_user(&find_user_by_login_address(&login_address)?)?;
+    if !user.is_login_principal(principal) {
+        return Err(UserError::UserNotFound.into());
+    }
+    Ok(user)
 }
 
 pub fn reset_password_user(login_address: &LoginAddress, password: String) -> Result<u64> {
-    USERS.with_borrow_mut(|users| {
-        let mut user_to_update = None;
+    let user_id = find_user_by_login_address(login_address)?;
+    mutate_user(user_id, |user| {
+        user.passwords.insert(login_address.index_key(), password);
+    })?;
+    Ok(user_id)
+}
 
-        for (_, user) in users.iter() {
-            if user.login == *login_address {
-                user_to_update = Some(User {
-                    hashed_password: Some(password.clone()),
-                    ..user
-                })
+/// Indexes the logins of users stored before the login index existed.
+pub fn rebuild_login_index() {
+    if !LOGIN_INDEX.with_borrow(|index| index.is_empty()) {
+        return;
+    }
+
+    USERS.with_borrow(|users| {
+        LOGIN_INDEX.with_borrow_mut(|index| {
+            for (id, user) in users.iter() {
+                for login in user.logins.iter() {
+                    index.insert(login.index_key(), id);
+                }
             }
+        })
+    });
+}
+
+fn update_login_index(
+    user_id: u64,
+    previous_logins: &HashSet<LoginAddress>,
+    logins: &HashSet<LoginAddress>,
+) -> Result<()> {
+    LOGIN_INDEX.with_borrow_mut(|index| {
+        let keys: HashSet<String> = logins.iter().map(|login| login.index_key()).collect();
+        if keys
+            .iter()
+            .any(|key| index.get(key).is_some_and(|id| id != user_id))
+        {
+            return Err(UserError::LoginAlreadyLinked.into());
         }
 
-        if let Some(user) = user_to_update {
-            let id = user.id;
-            users.insert(id, user);
-            Ok(id)
-        } else {
-            Err(UserError::UserNotFound.into())
+        for login in previous_logins {
+            let key = login.index_key();
+            if !keys.contains(&key) {
+                index.remove(&key);
+            }
+        }
+        for key in keys {
+            index.insert(key, user_id);
         }
+        Ok(())
     })
 }
@@ -0,0 +1,41 @@
+use crate::errors::Result;
+use crate::types::limits::{FiatLimit, FiatVolume};
+
+use super::storage::USER_VOLUMES;
+
+/// Records the volume of a paid order, replacing its reservation.
+pub fn record_volume(user_id: u64, order_id: Option<u64>, currency: &str, amount: u64) {
+    USER_VOLUMES.with_borrow_mut(|volumes| {
+        let mut volume = volumes.get(&user_id).unwrap_or_default();
+        volume.add(order_id, currency, amount, ic_cdk::api::time());
+        volumes.insert(user_id, volume);
+    });
+}
+
+pub fn reserve_volume(
+    user_id: u64,
+    order_id: u64,
+    currency: &str,
+    amount: u64,
+    limit: &FiatLimit,
+) -> Result<()> {
+    USER_VOLUMES.with_borrow_mut(|volumes| {
+        let mut volume = volumes.get(&user_id).unwrap_or_default();
+        volume.reserve(order_id, currency, amount, limit, ic_cdk::api::time())?;
+        volumes.insert(user_id, volume);
+        Ok(())
+    })
+}
+
+pub fn release_volume(user_id: u64, order_id: u64) {
+    USER_VOLUMES.with_borrow_mut(|volumes| {
+        if let Some(mut volume) = volumes.get(&user_id) {
+            volume.release(order_id);
+            volumes.insert(user_id, volume);
+        }
+    });
+}
+
+pub fn get_volume(user_id: u64) -> FiatVolume {
+    USER_VOLUMES.with_borrow(|volumes| volumes.get(&user_id).unwrap_or_default())
+}
@@ -0,0 +1,25 @@
+use candid::{CandidType, Deserialize, Principal};
+
+/// Operational roles granted to principals. Admins hold every role except `Treasury`,
+/// which is only held by explicit grant.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AccessRole {
+    Admin,      // role management, token registry and user removal
+    Operator,   // stuck order recovery, moderation and verification
+    Arbitrator, // dispute outcomes
+    Auditor,    // read access to balances, logs and counters
+    Treasury,   // canister fund transfers and fee withdrawals
+}
+
+impl AccessRole {
+    /// Whether holding this role grants `role`.
+    pub fn grants(&self, role: AccessRole) -> bool {
+        *self == role || (*self == AccessRole::Admin && role != AccessRole::Treasury)
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RoleAssignment {
+    pub principal: Principal,
+    pub roles: Vec<AccessRole>,
+}
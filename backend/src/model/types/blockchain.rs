@@ -4,7 +4,7 @@ use crate::errors::{BlockchainError, Result};
 
 use super::{
     evm::{chains, token},
-    icp,
+    icp, AddressType,
 };
 
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
@@ -15,6 +15,17 @@ pub enum Blockchain {
     Solana,
 }
 
+impl Blockchain {
+    pub fn address_type(&self) -> AddressType {
+        match self {
+            Blockchain::EVM { .. } => AddressType::EVM,
+            Blockchain::ICP { .. } => AddressType::ICP,
+            Blockchain::Bitcoin => AddressType::Bitcoin,
+            Blockchain::Solana => AddressType::Solana,
+        }
+    }
+}
+
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Crypto {
     pub blockchain: Blockchain,
@@ -0,0 +1,194 @@
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bitcoin::{
+    absolute::LockTime,
+    consensus::deserialize,
+    hashes::{sha256, Hash, HashEngine},
+    opcodes::{self, all::OP_RETURN},
+    script::Builder,
+    secp256k1::{Message, Secp256k1, XOnlyPublicKey},
+    sighash::{Prevouts, SighashCache},
+    sign_message::{signed_msg_hash, MessageSignature},
+    transaction::Version,
+    Address, Amount, CompressedPublicKey, Network, OutPoint, ScriptBuf, Sequence, Transaction,
+    TxIn, TxOut, Txid, Witness,
+};
+use ic_cdk::api::management_canister::bitcoin::BitcoinNetwork;
+
+use crate::errors::{BlockchainError, Result, SystemError, UserError};
+use crate::model::memory::heap::read_state;
+
+const BIP322_TAG: &[u8] = b"BIP0322-signed-message";
+
+pub fn get_network() -> Result<BitcoinNetwork> {
+    read_state(|s| s.bitcoin_network)
+        .ok_or_else(|| SystemError::InternalError("Bitcoin network is not set".to_string()).into())
+}
+
+pub fn parse_address(address: &str, network: BitcoinNetwork) -> Result<Address> {
+    let network = match network {
+        BitcoinNetwork::Mainnet => Network::Bitcoin,
+        BitcoinNetwork::Testnet => Network::Testnet,
+        BitcoinNetwork::Regtest => Network::Regtest,
+    };
+
+    Address::from_str(address)
+        .map_err(|_| BlockchainError::InvalidAddress)?
+        .require_network(network)
+        .map_err(|_| BlockchainError::InvalidAddress.into())
+}
+
+/// Verifies a base64 encoded `signature` of `message` by `address`.
+///
+/// P2PKH addresses use the legacy `signmessage` format, everything else the BIP-322
+/// simple format. BIP-322 is supported for P2WPKH and single-key P2TR addresses.
+pub fn verify_signature(address: &Address, message: &str, signature: &str) -> Result<()> {
+    let signature = STANDARD
+        .decode(signature)
+        .map_err(|_| UserError::InvalidSignature)?;
+    let script_pubkey = address.script_pubkey();
+
+    let is_valid = if script_pubkey.is_p2pkh() {
+        verify_legacy(address, message, &signature)
+    } else {
+        let witness: Witness = deserialize(&signature).map_err(|_| UserError::InvalidSignature)?;
+        verify_bip322(&script_pubkey, message, witness)
+    };
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(UserError::InvalidSignature.into())
+    }
+}
+
+fn verify_legacy(address: &Address, message: &str, signature: &[u8]) -> bool {
+    let Ok(signature) = MessageSignature::from_slice(signature) else {
+        return false;
+    };
+
+    signature
+        .is_signed_by_address(
+            &Secp256k1::verification_only(),
+            address,
+            signed_msg_hash(message),
+        )
+        .unwrap_or(false)
+}
+
+fn verify_bip322(script_pubkey: &ScriptBuf, message: &str, witness: Witness) -> bool {
+    let to_spend = bip322_to_spend(script_pubkey, message);
+    let to_sign = bip322_to_sign(&to_spend, witness.clone());
+    let secp = Secp256k1::verification_only();
+
+    if script_pubkey.is_p2wpkh() {
+        let (Some(signature), Some(pubkey), 2) = (witness.nth(0), witness.nth(1), witness.len())
+        else {
+            return false;
+        };
+        let Ok(pubkey) = CompressedPublicKey::from_slice(pubkey) else {
+            return false;
+        };
+        if ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash()) != *script_pubkey {
+            return false;
+        }
+        let Ok(signature) = bitcoin::ecdsa::Signature::from_slice(signature) else {
+            return false;
+        };
+        let Ok(sighash) = SighashCache::new(&to_sign).p2wpkh_signature_hash(
+            0,
+            script_pubkey,
+            Amount::ZERO,
+            signature.sighash_type,
+        ) else {
+            return false;
+        };
+
+        secp.verify_ecdsa(
+            &Message::from_digest(sighash.to_byte_array()),
+            &signature.signature,
+            &pubkey.0,
+        )
+        .is_ok()
+    } else if script_pubkey.is_p2tr() {
+        let (Some(signature), 1) = (witness.nth(0), witness.len()) else {
+            return false;
+        };
+        let Ok(signature) = bitcoin::taproot::Signature::from_slice(signature) else {
+            return false;
+        };
+        let Ok(output_key) = XOnlyPublicKey::from_slice(&script_pubkey.as_bytes()[2..34]) else {
+            return false;
+        };
+        let prevouts = [to_spend.output[0].clone()];
+        let Ok(sighash) = SighashCache::new(&to_sign).taproot_key_spend_signature_hash(
+            0,
+            &Prevouts::All(&prevouts),
+            signature.sighash_type,
+        ) else {
+            return false;
+        };
+
+        secp.verify_schnorr(
+            &signature.signature,
+            &Message::from_digest(sighash.to_byte_array()),
+            &output_key,
+        )
+        .is_ok()
+    } else {
+        false
+    }
+}
+
+fn bip322_message_hash(message: &str) -> [u8; 32] {
+    let tag = sha256::Hash::hash(BIP322_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    engine.input(message.as_bytes());
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+fn bip322_to_spend(script_pubkey: &ScriptBuf, message: &str) -> Transaction {
+    Transaction {
+        version: Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: Txid::all_zeros(),
+                vout: 0xFFFFFFFF,
+            },
+            script_sig: Builder::new()
+                .push_opcode(opcodes::OP_0)
+                .push_slice(bip322_message_hash(message))
+                .into_script(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: script_pubkey.clone(),
+        }],
+    }
+}
+
+fn bip322_to_sign(to_spend: &Transaction, witness: Witness) -> Transaction {
+    Transaction {
+        version: Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: to_spend.compute_txid(),
+                vout: 0,
+            },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness,
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+        }],
+    }
+}
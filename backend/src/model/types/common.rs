@@ -1,5 +1,4 @@
 use candid::{CandidType, Deserialize};
-use std::hash::Hash;
 
 use crate::errors::{Result, SystemError};
 use crate::helpers;
@@ -8,12 +7,13 @@ use crate::helpers;
 // Addresses
 // ---------
 
-#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum LoginAddress {
     Email { email: String },
     EVM { address: String },
     ICP { principal_id: String },
     Solana { address: String },
+    Bitcoin { address: String },
 }
 
 impl LoginAddress {
@@ -49,10 +49,39 @@ impl LoginAddress {
                 }
                 helpers::validate_solana_address(address)?;
             }
+            LoginAddress::Bitcoin { address } => {
+                if address.is_empty() {
+                    return Err(
+                        SystemError::InvalidInput("Bitcoin address is empty".to_string()).into(),
+                    );
+                }
+                helpers::validate_bitcoin_address(address)?;
+            }
         }
         Ok(())
     }
 
+    /// Normalised key of the login in the login index: case-insensitive formats are lowercased.
+    pub fn index_key(&self) -> String {
+        match self {
+            LoginAddress::Email { email } => format!("email:{}", email.trim().to_lowercase()),
+            LoginAddress::EVM { address } => format!("evm:{}", address.to_lowercase()),
+            LoginAddress::ICP { principal_id } => format!("icp:{}", principal_id),
+            LoginAddress::Solana { address } => format!("solana:{}", address),
+            LoginAddress::Bitcoin { address } => {
+                let lowercase = address.to_lowercase();
+                if ["bc1", "tb1", "bcrt1"]
+                    .iter()
+                    .any(|hrp| lowercase.starts_with(hrp))
+                {
+                    format!("bitcoin:{}", lowercase)
+                } else {
+                    format!("bitcoin:{}", address)
+                }
+            }
+        }
+    }
+
     pub fn to_transaction_address(&self) -> Result<TransactionAddress> {
         match self {
             LoginAddress::Email { .. } => Err(SystemError::InvalidInput(
@@ -71,6 +100,10 @@ impl LoginAddress {
                 address_type: AddressType::Solana,
                 address: address.clone(),
             }),
+            LoginAddress::Bitcoin { address } => Ok(TransactionAddress {
+                address_type: AddressType::Bitcoin,
+                address: address.clone(),
+            }),
         }
     }
 }
@@ -83,24 +116,12 @@ pub enum AddressType {
     Solana,
 }
 
-#[derive(CandidType, Deserialize, Clone, Debug, Eq)]
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct TransactionAddress {
     pub address_type: AddressType,
     pub address: String,
 }
 
-impl PartialEq for TransactionAddress {
-    fn eq(&self, other: &Self) -> bool {
-        self.address_type == other.address_type
-    }
-}
-
-impl Hash for TransactionAddress {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.address_type.hash(state);
-    }
-}
-
 impl TransactionAddress {
     pub fn validate(&self) -> Result<()> {
         if self.address.is_empty() {
@@ -118,8 +139,16 @@ impl TransactionAddress {
     }
 }
 
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum EvmSignatureType {
+    PersonalSign,
+    TypedData, // EIP-712
+}
+
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub struct AuthenticationData {
     pub password: Option<String>,  // For Email
-    pub signature: Option<String>, // For EVM
+    pub signature: Option<String>, // For EVM, Solana and Bitcoin
+    pub signature_type: Option<EvmSignatureType>,
+    pub contract_wallet: Option<bool>, // For EVM smart contract wallets, checked through EIP-1271
 }
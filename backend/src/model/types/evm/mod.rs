@@ -3,5 +3,6 @@ pub mod gas;
 pub mod logs;
 pub mod nonce;
 pub mod request;
+pub mod siwe;
 pub mod token;
 pub mod transaction;
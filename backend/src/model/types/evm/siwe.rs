@@ -0,0 +1,271 @@
+use std::str::FromStr;
+
+use candid::{CandidType, Deserialize};
+use chrono::{DateTime, SecondsFormat, Utc};
+use ethers_core::{
+    abi::{encode, Token},
+    types::{Address, U256},
+    utils::{keccak256, to_checksum},
+};
+use serde_json::json;
+
+use crate::errors::{BlockchainError, Result, UserError};
+
+const PREAMBLE_SUFFIX: &str = " wants you to sign in with your Ethereum account:";
+const SIWE_VERSION: &str = "1";
+
+const EIP712_DOMAIN_NAME: &str = "icRamp";
+const EIP712_DOMAIN_TYPE: &str = "EIP712Domain(string name,string version,uint256 chainId)";
+const EIP712_SIGN_IN_TYPE: &str = "SignIn(address wallet,string domain,string uri,string nonce,string issuedAt,string expirationTime)";
+
+/// Domain and URI the canister issues Sign-In with Ethereum messages for.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SiweConfig {
+    pub domain: String,
+    pub uri: String,
+    pub statement: Option<String>,
+    pub expiration_secs: u64,
+}
+
+impl Default for SiweConfig {
+    fn default() -> Self {
+        SiweConfig {
+            domain: "localhost".to_string(),
+            uri: "http://localhost".to_string(),
+            statement: Some("Sign in to icRamp".to_string()),
+            expiration_secs: 300, // 5 min
+        }
+    }
+}
+
+/// EIP-4361 message, see https://eips.ethereum.org/EIPS/eip-4361
+#[derive(Clone, Debug, PartialEq)]
+pub struct SiweMessage {
+    pub domain: String,
+    pub address: String,
+    pub statement: Option<String>,
+    pub uri: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub nonce: String,
+    pub issued_at: u64,               // nanoseconds
+    pub expiration_time: Option<u64>, // nanoseconds
+}
+
+impl SiweMessage {
+    pub fn new(config: &SiweConfig, address: &str, chain_id: u64, nonce: String) -> Result<Self> {
+        let address = Address::from_str(address).map_err(|_| BlockchainError::InvalidAddress)?;
+        let issued_at = ic_cdk::api::time();
+
+        Ok(SiweMessage {
+            domain: config.domain.clone(),
+            address: to_checksum(&address, None),
+            statement: config.statement.clone(),
+            uri: config.uri.clone(),
+            version: SIWE_VERSION.to_string(),
+            chain_id,
+            nonce,
+            issued_at,
+            expiration_time: Some(issued_at + config.expiration_secs * 1_000_000_000),
+        })
+    }
+
+    /// Checks that the message was issued for this canister and `address`, and that
+    /// it is used within its validity window.
+    pub fn validate(&self, config: &SiweConfig, address: &str, now: u64) -> Result<()> {
+        let expected_address =
+            Address::from_str(address).map_err(|_| BlockchainError::InvalidAddress)?;
+        let message_address =
+            Address::from_str(&self.address).map_err(|_| UserError::InvalidAuthMessage)?;
+
+        if self.domain != config.domain
+            || self.uri != config.uri
+            || self.version != SIWE_VERSION
+            || message_address != expected_address
+        {
+            return Err(UserError::InvalidAuthMessage.into());
+        }
+        if now < self.issued_at {
+            return Err(UserError::InvalidAuthMessage.into());
+        }
+        if let Some(expiration_time) = self.expiration_time {
+            if now >= expiration_time {
+                return Err(UserError::AuthMessageExpired.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// EIP-712 digest of the message, for wallets signing it as typed data.
+    pub fn eip712_hash(&self) -> Result<[u8; 32]> {
+        let wallet = Address::from_str(&self.address).map_err(|_| UserError::InvalidAuthMessage)?;
+
+        let domain_separator = keccak256(encode(&[
+            Token::FixedBytes(keccak256(EIP712_DOMAIN_TYPE).to_vec()),
+            Token::FixedBytes(keccak256(EIP712_DOMAIN_NAME).to_vec()),
+            Token::FixedBytes(keccak256(SIWE_VERSION).to_vec()),
+            Token::Uint(U256::from(self.chain_id)),
+        ]));
+        let struct_hash = keccak256(encode(&[
+            Token::FixedBytes(keccak256(EIP712_SIGN_IN_TYPE).to_vec()),
+            Token::Address(wallet),
+            Token::FixedBytes(keccak256(&self.domain).to_vec()),
+            Token::FixedBytes(keccak256(&self.uri).to_vec()),
+            Token::FixedBytes(keccak256(&self.nonce).to_vec()),
+            Token::FixedBytes(keccak256(format_timestamp(self.issued_at)).to_vec()),
+            Token::FixedBytes(keccak256(self.expiration_string()).to_vec()),
+        ]));
+
+        let mut digest_input = vec![0x19, 0x01];
+        digest_input.extend_from_slice(&domain_separator);
+        digest_input.extend_from_slice(&struct_hash);
+        Ok(keccak256(digest_input))
+    }
+
+    pub fn eip712_typed_data(&self) -> serde_json::Value {
+        json!({
+            "types": {
+                "EIP712Domain": [
+                    { "name": "name", "type": "string" },
+                    { "name": "version", "type": "string" },
+                    { "name": "chainId", "type": "uint256" },
+                ],
+                "SignIn": [
+                    { "name": "wallet", "type": "address" },
+                    { "name": "domain", "type": "string" },
+                    { "name": "uri", "type": "string" },
+                    { "name": "nonce", "type": "string" },
+                    { "name": "issuedAt", "type": "string" },
+                    { "name": "expirationTime", "type": "string" },
+                ],
+            },
+            "primaryType": "SignIn",
+            "domain": {
+                "name": EIP712_DOMAIN_NAME,
+                "version": SIWE_VERSION,
+                "chainId": self.chain_id,
+            },
+            "message": {
+                "wallet": self.address,
+                "domain": self.domain,
+                "uri": self.uri,
+                "nonce": self.nonce,
+                "issuedAt": format_timestamp(self.issued_at),
+                "expirationTime": self.expiration_string(),
+            },
+        })
+    }
+
+    fn expiration_string(&self) -> String {
+        self.expiration_time
+            .map(format_timestamp)
+            .unwrap_or_default()
+    }
+
+    pub fn parse(message: &str) -> Result<Self> {
+        let mut lines = message.lines();
+
+        let domain = lines
+            .next()
+            .and_then(|line| line.strip_suffix(PREAMBLE_SUFFIX))
+            .filter(|domain| !domain.is_empty())
+            .ok_or(UserError::InvalidAuthMessage)?
+            .to_string();
+        let address = lines
+            .next()
+            .filter(|address| address.starts_with("0x") && address.len() == 42)
+            .ok_or(UserError::InvalidAuthMessage)?
+            .to_string();
+        expect_line(lines.next(), "")?;
+
+        let mut statement = None;
+        let mut next = lines.next();
+        if let Some(line) = next.filter(|line| !line.starts_with("URI: ")) {
+            statement = Some(line.to_string());
+            expect_line(lines.next(), "")?;
+            next = lines.next();
+        }
+
+        let uri = field(next, "URI")?;
+        let version = field(lines.next(), "Version")?;
+        let chain_id = field(lines.next(), "Chain ID")?
+            .parse::<u64>()
+            .map_err(|_| UserError::InvalidAuthMessage)?;
+        let nonce = field(lines.next(), "Nonce")?;
+        if nonce.len() < 8 || !nonce.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(UserError::InvalidAuthMessage.into());
+        }
+        let issued_at = parse_timestamp(&field(lines.next(), "Issued At")?)?;
+        let expiration_time = match lines.next() {
+            Some(line) => Some(parse_timestamp(&field(Some(line), "Expiration Time")?)?),
+            None => None,
+        };
+        if lines.next().is_some() {
+            return Err(UserError::InvalidAuthMessage.into());
+        }
+
+        Ok(SiweMessage {
+            domain,
+            address,
+            statement,
+            uri,
+            version,
+            chain_id,
+            nonce,
+            issued_at,
+            expiration_time,
+        })
+    }
+}
+
+impl std::fmt::Display for SiweMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}{}", self.domain, PREAMBLE_SUFFIX)?;
+        writeln!(f, "{}", self.address)?;
+        writeln!(f)?;
+        if let Some(statement) = &self.statement {
+            writeln!(f, "{}", statement)?;
+            writeln!(f)?;
+        }
+        writeln!(f, "URI: {}", self.uri)?;
+        writeln!(f, "Version: {}", self.version)?;
+        writeln!(f, "Chain ID: {}", self.chain_id)?;
+        writeln!(f, "Nonce: {}", self.nonce)?;
+        write!(f, "Issued At: {}", format_timestamp(self.issued_at))?;
+        if let Some(expiration_time) = self.expiration_time {
+            write!(
+                f,
+                "\nExpiration Time: {}",
+                format_timestamp(expiration_time)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn expect_line(line: Option<&str>, expected: &str) -> Result<()> {
+    match line {
+        Some(line) if line == expected => Ok(()),
+        _ => Err(UserError::InvalidAuthMessage.into()),
+    }
+}
+
+pub(crate) fn field(line: Option<&str>, name: &str) -> Result<String> {
+    line.and_then(|line| line.strip_prefix(name))
+        .and_then(|rest| rest.strip_prefix(": "))
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_string())
+        .ok_or_else(|| UserError::InvalidAuthMessage.into())
+}
+
+pub(crate) fn format_timestamp(nanos: u64) -> String {
+    DateTime::<Utc>::from_timestamp_nanos(nanos as i64).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub(crate) fn parse_timestamp(value: &str) -> Result<u64> {
+    let timestamp = DateTime::parse_from_rfc3339(value)
+        .map_err(|_| UserError::InvalidAuthMessage)?
+        .timestamp_nanos_opt()
+        .ok_or(UserError::InvalidAuthMessage)?;
+    u64::try_from(timestamp).map_err(|_| UserError::InvalidAuthMessage.into())
+}
@@ -1,5 +1,9 @@
+use std::collections::HashMap;
+
 use candid::{CandidType, Deserialize};
 
+use crate::outcalls::xrc_rates::ExchangeRateMetadata;
+
 pub const CACHE_DURATION: u64 = 600 * 1_000_000_000; // 10 minutes
 
 #[derive(Debug, Clone, CandidType, Deserialize)]
@@ -32,4 +36,154 @@ impl ExchangeRateCache {
 
         None
     }
+
+    pub fn expires_within(&self, margin: u64) -> bool {
+        let time_elapsed = ic_cdk::api::time().saturating_sub(self.timestamp);
+        time_elapsed + margin >= CACHE_DURATION
+    }
+}
+
+/// Minimum quality an XRC rate must have to be cached and used for pricing.
+#[derive(Debug, Clone, CandidType, Deserialize)]
+pub struct RateQualityConfig {
+    pub min_sources: u64,
+    pub max_std_dev_bps: u64,
+    pub max_forex_age_secs: u64,
+}
+
+impl Default for RateQualityConfig {
+    fn default() -> Self {
+        RateQualityConfig {
+            min_sources: 3,
+            max_std_dev_bps: 200,                 // 2%
+            max_forex_age_secs: 4 * 24 * 60 * 60, // forex markets close on weekends
+        }
+    }
+}
+
+/// Maps token rate symbols (e.g. "USDT") to the fiat currency they are pegged to.
+#[derive(Debug, Clone, CandidType, Deserialize)]
+pub struct StablecoinRegistry {
+    pub pegs: HashMap<String, String>,
+    pub depeg_threshold_bps: u32,
+}
+
+impl StablecoinRegistry {
+    pub fn get_peg(&self, symbol: &str) -> Option<String> {
+        self.pegs.get(symbol).cloned()
+    }
+
+    pub fn is_depegged(&self, peg_rate: f64) -> bool {
+        (peg_rate - 1.).abs() * 10_000. > self.depeg_threshold_bps as f64
+    }
+}
+
+impl Default for StablecoinRegistry {
+    fn default() -> Self {
+        let pegs = [
+            ("USDT", "USD"),
+            ("USDC", "USD"),
+            ("DAI", "USD"),
+            ("EURC", "EUR"),
+        ]
+        .into_iter()
+        .map(|(symbol, peg)| (symbol.to_string(), peg.to_string()))
+        .collect();
+
+        StablecoinRegistry {
+            pegs,
+            depeg_threshold_bps: 100, // 1%
+        }
+    }
+}
+
+#[derive(Debug, Clone, CandidType, Deserialize)]
+pub struct RejectedRate {
+    pub base_symbol: String,
+    pub quote_symbol: String,
+    pub rate: f64,
+    pub metadata: ExchangeRateMetadata,
+    pub reasons: Vec<String>,
+    pub rejected_at: u64,
+}
+
+#[derive(Debug, Clone, CandidType, Deserialize)]
+pub struct RatePrefetchConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    pub refresh_margin_secs: u64,
+    pub daily_cycles_budget: u128,
+    pub backoff_base_secs: u64,
+    pub max_backoff_secs: u64,
+}
+
+impl Default for RatePrefetchConfig {
+    fn default() -> Self {
+        RatePrefetchConfig {
+            enabled: true,
+            interval_secs: 120,
+            refresh_margin_secs: 180,
+            daily_cycles_budget: 500_000_000_000, // 500 XRC calls
+            backoff_base_secs: 60,
+            max_backoff_secs: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, CandidType, Deserialize)]
+pub struct RatePrefetchStatus {
+    pub running: bool,
+    pub last_run: u64,
+    pub budget_window_start: u64,
+    pub cycles_spent: u128,
+    pub consecutive_backoffs: u32,
+    pub backoff_until: u64,
+}
+
+impl RatePrefetchStatus {
+    const BUDGET_WINDOW: u64 = 24 * 60 * 60 * 1_000_000_000; // 1 day
+    const RUN_LEASE: u64 = 10 * 60 * 1_000_000_000; // 10 min
+
+    /// Marks the prefetcher as running, unless it is already running or backing off.
+    ///
+    /// A run that never finished, e.g. because it trapped after an await, only holds
+    /// the flag for `RUN_LEASE`.
+    pub fn start(&mut self, now: u64) -> bool {
+        let leased = self.running && now < self.last_run.saturating_add(Self::RUN_LEASE);
+        if leased || now < self.backoff_until {
+            return false;
+        }
+        if now >= self.budget_window_start + Self::BUDGET_WINDOW {
+            self.budget_window_start = now;
+            self.cycles_spent = 0;
+        }
+        self.running = true;
+        self.last_run = now;
+        true
+    }
+
+    pub fn finish(&mut self) {
+        self.running = false;
+    }
+
+    /// Charges `cycles` against the daily budget. Returns false if the budget would be exceeded.
+    pub fn charge(&mut self, cycles: u128, budget: u128) -> bool {
+        if self.cycles_spent + cycles > budget {
+            return false;
+        }
+        self.cycles_spent += cycles;
+        true
+    }
+
+    pub fn back_off(&mut self, now: u64, base_secs: u64, max_secs: u64) {
+        let exponent = self.consecutive_backoffs.min(16);
+        let backoff_secs = base_secs.saturating_mul(1 << exponent).min(max_secs);
+        self.consecutive_backoffs += 1;
+        self.backoff_until = now + backoff_secs * 1_000_000_000;
+    }
+
+    pub fn reset_backoff(&mut self) {
+        self.consecutive_backoffs = 0;
+        self.backoff_until = 0;
+    }
 }
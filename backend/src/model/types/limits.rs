@@ -0,0 +1,174 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_stable_structures::{storable::Bound, Storable};
+
+use crate::{
+    errors::{Result, UserError},
+    model::memory::heap::LOCK_DURATION_TIME_SECONDS,
+};
+
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+const MONTH_NANOS: u64 = 30 * DAY_NANOS;
+// a reservation outlives the lock it was made for, which is released by then
+const RESERVATION_NANOS: u64 = 2 * LOCK_DURATION_TIME_SECONDS * 1_000_000_000;
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum VerificationLevel {
+    #[default]
+    Unverified,
+    Basic,
+    Verified,
+}
+
+/// Fiat amounts in cents, summed over rolling windows.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug)]
+pub struct FiatLimit {
+    pub daily: u64,   // last 24h
+    pub monthly: u64, // last 30 days
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct VerificationConfig {
+    pub attestors: HashSet<Principal>, // principals allowed to attest verification levels
+    pub limits: HashMap<VerificationLevel, HashMap<String, FiatLimit>>, // level -> currency -> limit
+}
+
+impl Default for VerificationConfig {
+    fn default() -> Self {
+        let tier = |daily: u64, monthly: u64| {
+            ["USD", "EUR", "GBP"]
+                .into_iter()
+                .map(|currency| (currency.to_string(), FiatLimit { daily, monthly }))
+                .collect::<HashMap<_, _>>()
+        };
+
+        VerificationConfig {
+            attestors: HashSet::new(),
+            limits: HashMap::from([
+                (VerificationLevel::Unverified, tier(50_000, 200_000)),
+                (VerificationLevel::Basic, tier(200_000, 1_000_000)),
+                (VerificationLevel::Verified, tier(1_000_000, 5_000_000)),
+            ]),
+        }
+    }
+}
+
+impl VerificationConfig {
+    /// Currencies without a configured limit for `level` cannot be traded at that level.
+    pub fn get_limit(&self, level: &VerificationLevel, currency: &str) -> Result<FiatLimit> {
+        self.limits
+            .get(level)
+            .and_then(|limits| limits.get(currency))
+            .copied()
+            .ok_or_else(|| UserError::TradingLimitNotConfigured(currency.to_string()).into())
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct VolumeEntry {
+    pub timestamp: u64,
+    pub currency: String,
+    pub amount: u64,
+    pub reserved_for: Option<u64>, // order id, while the order is locked but not paid
+}
+
+/// Fiat volume traded by a user over the last month, including the volume reserved by
+/// locked orders.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct FiatVolume(Vec<VolumeEntry>);
+
+impl FiatVolume {
+    /// Records settled volume, replacing the reservation made for `order_id` if any.
+    pub fn add(&mut self, order_id: Option<u64>, currency: &str, amount: u64, now: u64) {
+        if let Some(order_id) = order_id {
+            self.release(order_id);
+        }
+        self.push(None, currency, amount, now);
+    }
+
+    /// Reserves `amount` for `order_id` if it keeps the volume within `limit`. Checking and
+    /// reserving in one step keeps concurrent locks from exceeding the limit together.
+    pub fn reserve(
+        &mut self,
+        order_id: u64,
+        currency: &str,
+        amount: u64,
+        limit: &FiatLimit,
+        now: u64,
+    ) -> Result<()> {
+        self.release(order_id);
+        self.check(currency, amount, limit, now)?;
+        self.push(Some(order_id), currency, amount, now);
+        Ok(())
+    }
+
+    pub fn release(&mut self, order_id: u64) {
+        self.0.retain(|entry| entry.reserved_for != Some(order_id));
+    }
+
+    pub fn check(&self, currency: &str, amount: u64, limit: &FiatLimit, now: u64) -> Result<()> {
+        if self.daily(currency, now) + amount > limit.daily {
+            return Err(UserError::DailyLimitExceeded {
+                currency: currency.to_string(),
+                limit: limit.daily,
+            }
+            .into());
+        }
+        if self.monthly(currency, now) + amount > limit.monthly {
+            return Err(UserError::MonthlyLimitExceeded {
+                currency: currency.to_string(),
+                limit: limit.monthly,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    pub fn daily(&self, currency: &str, now: u64) -> u64 {
+        self.total_since(currency, now.saturating_sub(DAY_NANOS), now)
+    }
+
+    pub fn monthly(&self, currency: &str, now: u64) -> u64 {
+        self.total_since(currency, now.saturating_sub(MONTH_NANOS), now)
+    }
+
+    fn push(&mut self, reserved_for: Option<u64>, currency: &str, amount: u64, now: u64) {
+        self.0.retain(|entry| match entry.reserved_for {
+            Some(_) => entry.timestamp + RESERVATION_NANOS > now,
+            None => entry.timestamp + MONTH_NANOS > now,
+        });
+        self.0.push(VolumeEntry {
+            timestamp: now,
+            currency: currency.to_string(),
+            amount,
+            reserved_for,
+        });
+    }
+
+    fn total_since(&self, currency: &str, since: u64, now: u64) -> u64 {
+        self.0
+            .iter()
+            .filter(|entry| entry.currency == currency && entry.timestamp > since)
+            .filter(|entry| {
+                entry.reserved_for.is_none() || entry.timestamp + RESERVATION_NANOS > now
+            })
+            .map(|entry| entry.amount)
+            .sum()
+    }
+}
+
+impl Storable for FiatVolume {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
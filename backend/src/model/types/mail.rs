@@ -0,0 +1,95 @@
+use std::fmt;
+
+use candid::{CandidType, Deserialize};
+
+/// Mail delivery backend used for password reset emails.
+#[derive(CandidType, Deserialize, Clone, Default)]
+pub enum MailConfig {
+    /// No mail is sent and password resets are refused. The default until a backend is set.
+    #[default]
+    Disabled,
+    /// Prints emails, reset tokens included, to the canister log instead of sending them.
+    /// Only for local development and tests, never for a deployed canister.
+    Mock,
+    /// Posts emails to an HTTPS mail API, forwarded through the proxy.
+    Https {
+        api_url: String,
+        api_key: Option<String>, // moved into the secret store, only set in init and upgrade args
+        sender: String,
+        reset_url: String, // link to the frontend reset page
+    },
+}
+
+impl MailConfig {
+    /// Takes the API key out of the config, to be kept in the secret store.
+    pub fn take_api_key(&mut self) -> Option<String> {
+        match self {
+            MailConfig::Https { api_key, .. } => api_key.take(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for MailConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MailConfig::Disabled => write!(f, "Disabled"),
+            MailConfig::Mock => write!(f, "Mock"),
+            MailConfig::Https {
+                api_url,
+                sender,
+                reset_url,
+                ..
+            } => f
+                .debug_struct("Https")
+                .field("api_url", api_url)
+                .field("api_key", &"[REDACTED]")
+                .field("sender", sender)
+                .field("reset_url", reset_url)
+                .finish(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PasswordReset {
+    pub hashed_token: String,
+    pub expires_at: u64, // nanoseconds
+}
+
+impl PasswordReset {
+    pub(crate) const EXPIRATION_SECS: u64 = 1800; // 30 min
+
+    pub fn new(hashed_token: String) -> Self {
+        PasswordReset {
+            hashed_token,
+            expires_at: ic_cdk::api::time() + Self::EXPIRATION_SECS * 1_000_000_000,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        ic_cdk::api::time() >= self.expires_at
+    }
+}
+
+/// Pending proof that a user controls an email they want to link as a login.
+#[derive(Clone, Debug)]
+pub struct EmailVerification {
+    pub hashed_token: String,
+    pub expires_at: u64, // nanoseconds
+}
+
+impl EmailVerification {
+    pub(crate) const EXPIRATION_SECS: u64 = 1800; // 30 min
+
+    pub fn new(hashed_token: String) -> Self {
+        EmailVerification {
+            hashed_token,
+            expires_at: ic_cdk::api::time() + Self::EXPIRATION_SECS * 1_000_000_000,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        ic_cdk::api::time() >= self.expires_at
+    }
+}
@@ -1,28 +1,44 @@
+pub mod access;
 mod blockchain;
+pub mod btc;
 mod common;
 pub mod evm;
 pub mod exchange_rate;
 pub mod icp;
+pub mod limits;
+pub mod mail;
+pub mod moderation;
 pub mod orders;
 pub mod payment;
+pub mod rate_limit;
+pub mod reputation;
+pub mod secrets;
 pub mod session;
+pub mod solana;
+pub mod treasury;
 pub mod user;
+pub mod wallet_auth;
 
 pub use blockchain::{Blockchain, Crypto};
-pub use common::{AddressType, AuthenticationData, LoginAddress, TransactionAddress};
-pub use payment::providers::{contains_provider_type, PaymentProvider, PaymentProviderType};
+pub use common::{
+    AddressType, AuthenticationData, EvmSignatureType, LoginAddress, TransactionAddress,
+};
+pub use payment::providers::{
+    contains_provider_type, PaymentAccount, PaymentProvider, PaymentProviderType,
+};
 
 #[cfg(test)]
 mod tests {
     use crate::model::types::common::{LoginAddress, TransactionAddress};
-    use crate::model::types::user::User;
-    use crate::types::{common::AddressType, user::UserType, PaymentProvider};
+    use crate::model::types::{solana, user::User};
+    use crate::types::{
+        common::AddressType, evm::siwe::SiweMessage, user::UserType, PaymentProvider,
+    };
 
     use candid::Principal;
     use ethers_core::types::Address as EthAddress;
     use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
     use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
-    use std::collections::HashSet;
 
     #[test]
     fn test_stable_btree_map() {
@@ -30,32 +46,37 @@ mod tests {
         let mut map: StableBTreeMap<u64, User, _> =
             StableBTreeMap::init(memory_manager.get(MemoryId::new(0)));
 
-        let mut payment_providers = HashSet::new();
-        payment_providers.insert(PaymentProvider::PayPal {
-            id: "paypal_id".to_string(),
-        });
-
         let login_address = LoginAddress::EVM {
             address: (format!("{:#x}", EthAddress::random())),
         };
 
         let mut user = User::new(UserType::Offramper, login_address.clone(), None).unwrap();
+        user.add_payment_account(
+            PaymentProvider::PayPal {
+                id: "paypal_id".to_string(),
+            },
+            "personal".to_string(),
+        )
+        .unwrap();
         map.insert(0, user.clone());
 
         let retrieved_user = map.get(&0).unwrap();
         assert_eq!(user.payment_providers, retrieved_user.payment_providers);
         assert_eq!(user.fiat_amounts, retrieved_user.fiat_amounts);
-        assert_eq!(user.score, retrieved_user.score);
+        assert_eq!(user.scores, retrieved_user.scores);
 
         // Update user
         let mut updated_user = retrieved_user.clone();
         updated_user
-            .payment_providers
-            .insert(PaymentProvider::Revolut {
-                id: "revolut_id".to_string(),
-                scheme: "scheme".to_string(),
-                name: Some("name".to_string()),
-            });
+            .add_payment_account(
+                PaymentProvider::Revolut {
+                    id: "revolut_id".to_string(),
+                    scheme: "scheme".to_string(),
+                    name: Some("name".to_string()),
+                },
+                "business".to_string(),
+            )
+            .unwrap();
         map.insert(updated_user.id, updated_user.clone());
 
         let retrieved_updated_user = map.get(&updated_user.id).unwrap();
@@ -63,27 +84,21 @@ mod tests {
             updated_user.payment_providers,
             retrieved_updated_user.payment_providers
         );
+        assert_eq!(retrieved_updated_user.payment_providers.len(), 2);
 
         // Add address
         let new_address = TransactionAddress {
             address_type: AddressType::ICP,
             address: Principal::anonymous().to_string(),
         };
-        if let Some(existing_address) = user.addresses.take(&new_address) {
-            ic_cdk::println!(
-                "updating address {:?} to {:?}",
-                existing_address,
-                new_address
-            )
-        }
-        updated_user.addresses.insert(new_address.clone());
+        updated_user
+            .add_address(new_address.clone(), "icp".to_string(), false)
+            .unwrap();
 
         map.insert(updated_user.id, updated_user.clone());
 
         let retrieved_user_with_new_address = map.get(&updated_user.id).unwrap();
-        assert!(retrieved_user_with_new_address
-            .addresses
-            .contains(&new_address));
+        assert!(retrieved_user_with_new_address.has_address(&new_address));
     }
 
     #[test]
@@ -98,35 +113,35 @@ mod tests {
             address_type: AddressType::ICP,
             address: "2chl6-4hpzw-vqaaa-aaaaa-c".to_string(),
         };
-        if let Some(existing_address) = user.addresses.take(&new_address) {
-            ic_cdk::println!(
-                "updating address {:?} to {:?}",
-                existing_address,
-                new_address
-            )
-        }
-        user.addresses.insert(new_address.clone());
-        assert!(user.addresses.contains(&new_address));
+        user.add_address(new_address.clone(), "personal".to_string(), false)
+            .unwrap();
+        assert!(user.has_address(&new_address));
+        assert_eq!(user.default_address(&AddressType::ICP), Some(&new_address));
 
-        // Attempt to add the same address type should replace the old one
-        let updated_address = TransactionAddress {
+        // A second address of the same type is kept alongside the first one
+        let second_address = TransactionAddress {
             address_type: AddressType::ICP,
             address: Principal::anonymous().to_string(),
         };
+        user.add_address(second_address.clone(), "business".to_string(), true)
+            .unwrap();
+        assert_eq!(user.addresses.len(), 3);
+        assert!(user.has_address(&new_address));
+        assert_eq!(
+            user.default_address(&AddressType::ICP),
+            Some(&second_address)
+        );
 
-        if let Some(existing_address) = user.addresses.take(&updated_address) {
-            ic_cdk::println!(
-                "updating address {:?} to {:?}",
-                existing_address,
-                new_address
-            )
-        }
-        user.addresses.insert(updated_address.clone());
-        assert_eq!(user.addresses.len(), 2);
+        // Removing the default promotes the remaining address of the type
+        user.remove_address(&second_address).unwrap();
+        assert_eq!(user.default_address(&AddressType::ICP), Some(&new_address));
         assert_eq!(
-            user.addresses.get(&new_address).unwrap().address,
-            updated_address.address
+            user.resolve_address(None, &AddressType::ICP).unwrap(),
+            new_address
         );
+        assert!(user
+            .resolve_address(Some(second_address), &AddressType::ICP)
+            .is_err());
     }
 
     #[test]
@@ -154,4 +169,748 @@ mod tests {
         assert_eq!(user1.addresses, retrieved_user1.addresses);
         assert_eq!(user2.addresses, retrieved_user2.addresses);
     }
+
+    #[test]
+    fn test_siwe_message_roundtrip() {
+        let message = SiweMessage {
+            domain: "localhost".to_string(),
+            address: "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed".to_string(),
+            statement: Some("Sign in to icRamp".to_string()),
+            uri: "http://localhost".to_string(),
+            version: "1".to_string(),
+            chain_id: 1,
+            nonce: "32891756abcdef".to_string(),
+            issued_at: 1_700_000_000_000_000_000,
+            expiration_time: Some(1_700_000_300_000_000_000),
+        };
+
+        let text = message.to_string();
+        assert!(text.contains("Issued At: 2023-11-14T22:13:20Z"));
+        assert_eq!(SiweMessage::parse(&text).unwrap(), message);
+
+        let tampered = text.replace("Chain ID: 1", "Chain ID: one");
+        assert!(SiweMessage::parse(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_solana_signature_verification() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let address = bs58::encode(signing_key.verifying_key().as_bytes()).into_string();
+        let message = "localhost wants you to sign in with your Solana account:";
+        let signature = bs58::encode(signing_key.sign(message.as_bytes()).to_bytes()).into_string();
+
+        assert!(solana::verify_signature(&address, message, &signature).is_ok());
+        assert!(solana::verify_signature(&address, "tampered", &signature).is_err());
+        assert!(solana::verify_signature("not-base58!", message, &signature).is_err());
+    }
+
+    #[test]
+    fn test_quote_expiry_and_slippage() {
+        use crate::types::{orders::OrderQuote, PaymentProviderType};
+
+        let quote = OrderQuote {
+            id: "quote".to_string(),
+            order_id: 1,
+            user_id: 2,
+            provider_type: PaymentProviderType::PayPal,
+            currency: "USD".to_string(),
+            price: 10_000,
+            offramper_fee: 20,
+            crypto_amount: 1_000,
+            crypto_fee: 10,
+            net_crypto_amount: 990,
+            created_at: 1_000,
+            expires_at: 2_000,
+        };
+
+        assert!(!quote.is_expired_at(1_999));
+        assert!(quote.is_expired_at(2_000));
+
+        assert!(quote.check_slippage(10_100, 100).is_ok());
+        assert!(quote.check_slippage(9_900, 100).is_ok());
+        assert!(quote.check_slippage(10_101, 100).is_err());
+        assert!(quote.check_slippage(9_899, 100).is_err());
+        assert!(quote.check_slippage(10_000, 0).is_ok());
+    }
+
+    #[test]
+    fn test_heap_saved_before_new_settings_decodes() {
+        use crate::model::memory::heap::upgrade::{take_legacy_secrets, SerializableHeap};
+        use crate::types::{mail::MailConfig, secrets::SecretKey};
+        use candid::{CandidType, Decode, Encode};
+        use ic_cdk::api::management_canister::ecdsa::{EcdsaCurve, EcdsaKeyId};
+        use ic_stable_structures::Storable;
+        use std::collections::HashMap;
+
+        #[derive(CandidType)]
+        struct BaselinePayPal {
+            access_token: Option<String>,
+            token_expiration: Option<u64>,
+            client_id: String,
+            client_secret: String,
+            api_url: String,
+        }
+
+        #[derive(CandidType)]
+        struct BaselineRevolut {
+            access_token: Option<String>,
+            token_expiration: Option<u64>,
+            client_id: String,
+            api_url: String,
+            proxy_url: String,
+            private_key_der: Vec<u8>,
+            kid: String,
+            tan: String,
+        }
+
+        #[derive(CandidType)]
+        struct BaselineState {
+            chains: HashMap<u64, ()>,
+            ecdsa_pub_key: Option<Vec<u8>>,
+            ecdsa_key_id: EcdsaKeyId,
+            evm_address: Option<String>,
+            paypal: BaselinePayPal,
+            revolut: BaselineRevolut,
+            proxy_url: String,
+            icp_tokens: HashMap<Principal, ()>,
+        }
+
+        #[derive(CandidType)]
+        struct BaselineHeap {
+            user_id_counter: u64,
+            order_id_counter: u64,
+            locked_order_timers: HashMap<u64, u64>,
+            exchange_rate_cache: HashMap<(String, String), ()>,
+            state: BaselineState,
+        }
+
+        let baseline = BaselineHeap {
+            user_id_counter: 3,
+            order_id_counter: 7,
+            locked_order_timers: HashMap::new(),
+            exchange_rate_cache: HashMap::new(),
+            state: BaselineState {
+                chains: HashMap::new(),
+                ecdsa_pub_key: None,
+                ecdsa_key_id: EcdsaKeyId {
+                    curve: EcdsaCurve::Secp256k1,
+                    name: "test_key_1".to_string(),
+                },
+                evm_address: None,
+                paypal: BaselinePayPal {
+                    access_token: None,
+                    token_expiration: None,
+                    client_id: "client".to_string(),
+                    client_secret: "secret".to_string(),
+                    api_url: "https://api-m.sandbox.paypal.com".to_string(),
+                },
+                revolut: BaselineRevolut {
+                    access_token: None,
+                    token_expiration: None,
+                    client_id: "client".to_string(),
+                    api_url: "https://sandbox-oba.revolut.com".to_string(),
+                    proxy_url: "https://proxy".to_string(),
+                    private_key_der: vec![1, 2, 3],
+                    kid: "kid".to_string(),
+                    tan: "tan".to_string(),
+                },
+                proxy_url: "https://proxy".to_string(),
+                icp_tokens: HashMap::new(),
+            },
+        };
+
+        let bytes = Encode!(&baseline).unwrap();
+        assert!(Decode!(&bytes, SerializableHeap).is_err());
+        let heap = SerializableHeap::from_bytes(bytes.into());
+        let heap = SerializableHeap::from_bytes(heap.to_bytes());
+        assert_eq!(heap.user_id_counter, 3);
+        assert_eq!(heap.order_id_counter, 7);
+
+        let mut state = heap.state;
+        assert!(matches!(state.mail, MailConfig::Disabled));
+        assert!(state.bitcoin_network.is_none());
+        assert!(state.rate_limits.enabled);
+        assert_eq!(state.rate_limits.limits.len(), 4);
+        assert!(state.access_roles.is_empty());
+        assert_eq!(state.treasury.threshold, 2);
+        assert!(state.treasury.signers.is_empty());
+        assert_eq!(state.paypal.client_secret.as_deref(), Some("secret"));
+        assert_eq!(state.revolut.private_key_der, Some(vec![1, 2, 3]));
+
+        let legacy_secrets = take_legacy_secrets(&mut state);
+        assert!(state.paypal.client_secret.is_none());
+        assert!(state.revolut.private_key_der.is_none());
+        assert_eq!(legacy_secrets.len(), 2);
+        assert_eq!(
+            legacy_secrets[0],
+            (SecretKey::PaypalClientSecret, b"secret".to_vec())
+        );
+        assert_eq!(
+            legacy_secrets[1],
+            (SecretKey::RevolutPrivateKey, vec![1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn test_bip322_p2wpkh_vectors() {
+        use crate::model::types::btc;
+        use ic_cdk::api::management_canister::bitcoin::BitcoinNetwork;
+
+        // test vectors from BIP-322
+        let address = btc::parse_address(
+            "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l",
+            BitcoinNetwork::Mainnet,
+        )
+        .unwrap();
+        let empty = "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+        let hello = "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+
+        assert!(btc::verify_signature(&address, "", empty).is_ok());
+        assert!(btc::verify_signature(&address, "Hello World", hello).is_ok());
+        assert!(btc::verify_signature(&address, "Hello World", empty).is_err());
+        assert!(btc::verify_signature(&address, "", hello).is_err());
+    }
+
+    #[test]
+    fn test_bip322_p2tr_vector() {
+        use crate::model::types::btc;
+        use ic_cdk::api::management_canister::bitcoin::BitcoinNetwork;
+
+        // test vector from BIP-322
+        let address = btc::parse_address(
+            "bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3",
+            BitcoinNetwork::Mainnet,
+        )
+        .unwrap();
+        let hello = "AUHd69PrJQEv+oKTfZ8l+WROBHuy9HKrbFCJu7U1iK2iiEy1vMU5EfMtjc+VSHM7aU0SDbak5IUZRVno2P5mjSafAQ==";
+
+        assert!(btc::verify_signature(&address, "Hello World", hello).is_ok());
+        assert!(btc::verify_signature(&address, "Hello World!", hello).is_err());
+    }
+
+    #[test]
+    fn test_legacy_bitcoin_message_signature() {
+        use crate::model::types::btc;
+        use base64::{engine::general_purpose::STANDARD, Engine};
+        use bitcoin::{
+            hashes::Hash,
+            secp256k1::{Message, Secp256k1, SecretKey},
+            sign_message::{signed_msg_hash, MessageSignature},
+            Address, CompressedPublicKey, Network,
+        };
+        use ic_cdk::api::management_canister::bitcoin::BitcoinNetwork;
+
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let public_key = CompressedPublicKey(secret_key.public_key(&secp));
+        let address = Address::p2pkh(public_key, Network::Bitcoin).to_string();
+
+        let message = "Hello World";
+        let digest = Message::from_digest(signed_msg_hash(message).to_byte_array());
+        let signature =
+            MessageSignature::new(secp.sign_ecdsa_recoverable(&digest, &secret_key), true);
+        let signature = STANDARD.encode(signature.serialize());
+
+        let address = btc::parse_address(&address, BitcoinNetwork::Mainnet).unwrap();
+        assert!(btc::verify_signature(&address, message, &signature).is_ok());
+        assert!(btc::verify_signature(&address, "tampered", &signature).is_err());
+        assert!(btc::parse_address(&address.to_string(), BitcoinNetwork::Testnet).is_err());
+    }
+
+    #[test]
+    fn test_full_user_sessions_fit_their_bound() {
+        use crate::types::session::{Session, UserSessions};
+        use ic_stable_structures::{storable::Bound, Storable};
+
+        let sessions = UserSessions(
+            (0..Session::MAX_SESSIONS)
+                .map(|i| Session {
+                    id: format!("{:016x}", i),
+                    name: "n".repeat(Session::MAX_NAME_LENGTH),
+                    token: "t".repeat(64),
+                    created_at: u64::MAX,
+                    expires_at: u64::MAX,
+                    principal: Some(Principal::from_slice(&[0xff; 29])),
+                })
+                .collect(),
+        );
+
+        let Bound::Bounded { max_size, .. } = UserSessions::BOUND else {
+            panic!("sessions should be bounded");
+        };
+        assert!(sessions.to_bytes().len() <= max_size as usize);
+    }
+
+    #[test]
+    fn test_email_logins_keep_their_own_password() {
+        let first = LoginAddress::Email {
+            email: "first@example.com".to_string(),
+        };
+        let second = LoginAddress::Email {
+            email: "Second@Example.com".to_string(),
+        };
+
+        let mut user = User::new(
+            UserType::Onramper,
+            first.clone(),
+            Some("hash-1".to_string()),
+        )
+        .unwrap();
+        user.link_login(second.clone(), Some("hash-2".to_string()))
+            .unwrap();
+        assert_eq!(user.passwords.get(&first.index_key()).unwrap(), "hash-1");
+        assert_eq!(user.passwords.get(&second.index_key()).unwrap(), "hash-2");
+
+        user.unlink_login(&first).unwrap();
+        assert!(!user.passwords.contains_key(&first.index_key()));
+        assert_eq!(user.passwords.get(&second.index_key()).unwrap(), "hash-2");
+    }
+
+    #[test]
+    fn test_locked_volume_counts_against_limits() {
+        use crate::types::limits::{FiatLimit, FiatVolume, VerificationConfig, VerificationLevel};
+
+        let limit = FiatLimit {
+            daily: 10_000,
+            monthly: 100_000,
+        };
+        let now = 1_000_000_000_000;
+        let mut volume = FiatVolume::default();
+
+        volume.reserve(1, "EUR", 60_00, &limit, now).unwrap();
+        assert!(volume.reserve(2, "EUR", 60_00, &limit, now).is_err());
+        volume.reserve(2, "USD", 60_00, &limit, now).unwrap();
+
+        volume.release(1);
+        volume.reserve(2, "EUR", 60_00, &limit, now).unwrap();
+        volume.add(Some(2), "EUR", 60_00, now);
+        assert_eq!(volume.daily("EUR", now), 60_00);
+        assert_eq!(volume.daily("USD", now), 0);
+
+        let config = VerificationConfig::default();
+        assert!(config
+            .get_limit(&VerificationLevel::Verified, "EUR")
+            .is_ok());
+        assert!(config
+            .get_limit(&VerificationLevel::Verified, "XYZ")
+            .is_err());
+    }
+
+    #[test]
+    fn test_reputation_saved_before_roles_decodes() {
+        use crate::types::reputation::{Reputation, UserReputations};
+        use ic_stable_structures::Storable;
+        use std::borrow::Cow;
+
+        let mut reputation = Reputation::default();
+        reputation.add_rating(4);
+        let bytes = candid::encode_one(&reputation).unwrap();
+
+        let reputations = UserReputations::from_bytes(Cow::Owned(bytes));
+        assert_eq!(reputations.0[&UserType::Onramper].rating_sum, 4);
+        assert!(!reputations.0.contains_key(&UserType::Offramper));
+    }
+
+    #[test]
+    fn test_full_user_fits_its_bound() {
+        use crate::types::moderation::ModerationStatus;
+        use ic_stable_structures::{storable::Bound, Storable};
+
+        let email = |i: usize| LoginAddress::Email {
+            email: format!(
+                "{}{}@{}.com",
+                i,
+                "a".repeat(63),
+                vec!["b".repeat(63); 3].join(".")
+            ),
+        };
+        let mut user = User::new(UserType::Onramper, email(0), Some("h".repeat(100))).unwrap();
+        for i in 1..User::MAX_LOGINS {
+            user.link_login(email(i), Some("h".repeat(100))).unwrap();
+        }
+        assert!(user.link_login(email(99), Some("h".repeat(100))).is_err());
+
+        let label = "l".repeat(User::MAX_LABEL_LENGTH);
+        for i in 0..User::MAX_ADDRESSES {
+            let address = TransactionAddress {
+                address_type: AddressType::Bitcoin,
+                address: format!("{:090}", i),
+            };
+            user.add_address(address, label.clone(), false).unwrap();
+        }
+        let extra = TransactionAddress {
+            address_type: AddressType::Bitcoin,
+            address: "extra".to_string(),
+        };
+        assert!(user.add_address(extra, label.clone(), false).is_err());
+        assert!(user
+            .add_address(
+                user.addresses[0].address.clone(),
+                label.clone() + "l",
+                false
+            )
+            .is_err());
+
+        let detail = "d".repeat(128);
+        for i in 0..User::MAX_PAYMENT_ACCOUNTS {
+            let provider = PaymentProvider::Revolut {
+                scheme: detail.clone(),
+                id: format!("{:0128}", i),
+                name: Some(detail.clone()),
+            };
+            provider.validate().unwrap();
+            user.add_payment_account(provider, label.clone()).unwrap();
+        }
+        let extra = PaymentProvider::PayPal {
+            id: "extra".to_string(),
+        };
+        assert!(user.add_payment_account(extra, label.clone()).is_err());
+
+        user.add_role(UserType::Offramper);
+        user.evm_auth_message = Some("m".repeat(1000));
+        user.moderation = ModerationStatus::Banned {
+            reason: "r".repeat(ModerationStatus::MAX_REASON_LEN),
+            by: Principal::from_slice(&[0xff; 29]),
+        };
+
+        let Bound::Bounded { max_size, .. } = User::BOUND else {
+            panic!("users should be bounded");
+        };
+        assert!(user.to_bytes().len() <= max_size as usize);
+    }
+
+    #[test]
+    fn test_admin_grants_every_role_but_treasury() {
+        use crate::types::access::AccessRole;
+
+        let roles = [
+            AccessRole::Admin,
+            AccessRole::Operator,
+            AccessRole::Arbitrator,
+            AccessRole::Auditor,
+            AccessRole::Treasury,
+        ];
+        for role in roles {
+            assert!(role.grants(role));
+            assert_eq!(AccessRole::Admin.grants(role), role != AccessRole::Treasury);
+        }
+        assert!(!AccessRole::Operator.grants(AccessRole::Admin));
+        assert!(!AccessRole::Treasury.grants(AccessRole::Operator));
+    }
+
+    #[test]
+    fn test_token_bucket_refills_over_its_window() {
+        use crate::types::rate_limit::{BucketConfig, RateLimitKey, TokenBucket};
+
+        let config = BucketConfig {
+            capacity: 2,
+            window_secs: 60,
+        };
+        let mut bucket = TokenBucket::new(&config, 0);
+        assert!(bucket.try_take(&config, 0).is_ok());
+        assert!(bucket.try_take(&config, 0).is_ok());
+        assert_eq!(bucket.try_take(&config, 0), Err(30));
+
+        // one token every 30 seconds
+        assert!(bucket.try_take(&config, 30_000_000_000).is_ok());
+        assert!(!bucket.is_full(&config, 30_000_000_000));
+        assert!(bucket.is_full(&config, 90_000_000_000));
+
+        let anonymous = config.for_key(&RateLimitKey::Anonymous);
+        assert!(anonymous.capacity > config.capacity);
+        assert_eq!(config.for_key(&RateLimitKey::User(1)).capacity, 2);
+    }
+
+    #[test]
+    fn test_treasury_proposal_lifecycle() {
+        use crate::types::treasury::{
+            ProposalStatus, TreasuryAction, TreasuryConfig, TreasuryProposal,
+        };
+        use std::collections::HashSet;
+
+        const SECS: u64 = 1_000_000_000;
+        let signer = |i: u8| Principal::from_slice(&[i; 29]);
+        let mut config = TreasuryConfig {
+            signers: HashSet::from([signer(1), signer(2), signer(3)]),
+            threshold: 2,
+            expiry_secs: 100,
+            timelock_secs: 10,
+        };
+        let action = TreasuryAction::EvmFeeWithdrawal {
+            chain_id: 1,
+            amount: 1,
+            token: None,
+        };
+        let new_proposal = |config: &TreasuryConfig| {
+            TreasuryProposal::new(1, action.clone(), signer(1), config, 0)
+        };
+
+        // threshold and duplicate approvals
+        let mut proposal = new_proposal(&config);
+        proposal
+            .approve(signer(1), config.timelock_secs, 0)
+            .unwrap();
+        assert!(proposal
+            .approve(signer(1), config.timelock_secs, 0)
+            .is_err());
+        assert_eq!(proposal.status, ProposalStatus::Pending);
+        assert!(proposal.check_executable(&config, 50 * SECS).is_err());
+        proposal
+            .approve(signer(2), config.timelock_secs, 5 * SECS)
+            .unwrap();
+        assert_eq!(proposal.status, ProposalStatus::Approved { at: 5 * SECS });
+
+        // timelock, then an execution window as long as the approval window
+        assert!(proposal.check_executable(&config, 14 * SECS).is_err());
+        proposal.check_executable(&config, 15 * SECS).unwrap();
+        proposal.check_executable(&config, 114 * SECS).unwrap();
+        assert!(proposal.check_executable(&config, 115 * SECS).is_err());
+
+        // approvals of removed signers no longer count
+        config.signers.remove(&signer(2));
+        assert!(proposal.check_executable(&config, 15 * SECS).is_err());
+
+        // approvals close once the proposal expires
+        let mut proposal = new_proposal(&config);
+        proposal
+            .approve(signer(1), config.timelock_secs, 0)
+            .unwrap();
+        assert!(proposal
+            .approve(signer(3), config.timelock_secs, 100 * SECS)
+            .is_err());
+    }
+
+    #[test]
+    fn test_saved_mail_api_key_moves_out_of_the_config() {
+        use crate::types::mail::MailConfig;
+        use candid::{CandidType, Deserialize};
+
+        #[derive(CandidType, Deserialize)]
+        enum SavedMailConfig {
+            Disabled,
+            Mock,
+            Https {
+                api_url: String,
+                api_key: String,
+                sender: String,
+                reset_url: String,
+            },
+        }
+
+        let saved = SavedMailConfig::Https {
+            api_url: "api.mail.example".to_string(),
+            api_key: "key".to_string(),
+            sender: "noreply@example.com".to_string(),
+            reset_url: "https://example.com/reset".to_string(),
+        };
+        let mut mail: MailConfig = candid::decode_one(&candid::encode_one(saved).unwrap()).unwrap();
+        assert_eq!(mail.take_api_key(), Some("key".to_string()));
+        assert_eq!(mail.take_api_key(), None);
+    }
+
+    #[test]
+    fn test_secrets_stay_out_of_debug_output() {
+        use crate::model::memory::stable::{secrets, storage::SECRETS};
+        use crate::types::secrets::{Secret, SecretKey};
+
+        let secret = Secret {
+            value: b"hunter2".to_vec(),
+            version: 3,
+            rotated_at: 0,
+        };
+        let debug = format!("{:?}", secret);
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains("version: 3"));
+
+        SECRETS.with_borrow_mut(|stored| {
+            stored.insert(SecretKey::MailApiKey.as_str().to_string(), secret)
+        });
+        assert!(secrets::contains_secret(SecretKey::MailApiKey));
+        assert_eq!(
+            secrets::get_secret(SecretKey::MailApiKey).unwrap(),
+            b"hunter2".to_vec()
+        );
+        assert!(secrets::get_secret(SecretKey::PaypalClientSecret).is_err());
+    }
+
+    fn test_order() -> crate::types::orders::Order {
+        use crate::types::{orders::Order, Blockchain, Crypto, PaymentProviderType};
+        use std::collections::HashMap;
+
+        let provider = PaymentProvider::PayPal {
+            id: "payee@example.com".to_string(),
+        };
+        Order {
+            id: 1,
+            created_at: 0,
+            currency: "EUR".to_string(),
+            offramper_user_id: 1,
+            offramper_address: TransactionAddress {
+                address_type: AddressType::ICP,
+                address: Principal::anonymous().to_string(),
+            },
+            offramper_accounts: Some(HashMap::from([(
+                PaymentProviderType::PayPal,
+                provider.account_id(),
+            )])),
+            offramper_providers: HashMap::from([(PaymentProviderType::PayPal, provider)]),
+            crypto: Crypto::new(
+                Blockchain::ICP {
+                    ledger_principal: Principal::anonymous(),
+                },
+                None,
+                1_000,
+                10,
+            ),
+            processing: false,
+            payee: None,
+        }
+    }
+
+    #[test]
+    fn test_order_view_redacts_for_outsiders() {
+        use crate::types::orders::{LockedOrder, Onramper, OrderState, OrderViewer};
+        use crate::types::PaymentProviderType;
+
+        let order = test_order();
+        let state = OrderState::Created(order.clone());
+        let OrderState::Created(public) = state.clone().view(&OrderViewer::Public) else {
+            panic!("order should stay created");
+        };
+        assert!(public.offramper_address.address.is_empty());
+        assert!(public.offramper_accounts.is_none());
+        assert_ne!(
+            public.offramper_providers[&PaymentProviderType::PayPal],
+            order.offramper_providers[&PaymentProviderType::PayPal]
+        );
+        let OrderState::Created(own) = state.view(&OrderViewer::User(1)) else {
+            panic!("order should stay created");
+        };
+        assert_eq!(own.offramper_address, order.offramper_address);
+
+        let onramper_provider = PaymentProvider::PayPal {
+            id: "payer@example.com".to_string(),
+        };
+        let onramper_address = TransactionAddress {
+            address_type: AddressType::ICP,
+            address: "2chl6-4hpzw-vqaaa-aaaaa-c".to_string(),
+        };
+        let locked = OrderState::Locked(LockedOrder {
+            base: order,
+            locked_at: 0,
+            price: 100,
+            offramper_fee: 1,
+            onramper: Onramper::new(2, onramper_provider.clone(), onramper_address.clone()),
+            revolut_consent: None,
+            payment_id: Some("payment".to_string()),
+            payment_done: false,
+            paid_at: None,
+            uncommited: false,
+        });
+
+        let OrderState::Locked(outsider) = locked.clone().view(&OrderViewer::User(3)) else {
+            panic!("order should stay locked");
+        };
+        assert!(outsider.onramper.address.address.is_empty());
+        assert_ne!(outsider.onramper.provider, onramper_provider);
+        assert!(outsider.payment_id.is_none());
+
+        assert!(!OrderViewer::Public.sees_details_of(2));
+        assert!(!OrderViewer::User(3).sees_details_of(2));
+        for viewer in [OrderViewer::User(2), OrderViewer::Admin] {
+            assert!(viewer.sees_details_of(2));
+            let OrderState::Locked(party) = locked.clone().view(&viewer) else {
+                panic!("order should stay locked");
+            };
+            assert_eq!(party.onramper.address, onramper_address);
+            assert_eq!(party.onramper.provider, onramper_provider);
+        }
+    }
+
+    #[test]
+    fn test_payee_survives_sealing_and_resealing() {
+        use crate::model::memory::stable::storage::SECRETS;
+        use crate::types::{
+            secrets::{Secret, SecretKey},
+            PaymentProviderType,
+        };
+
+        SECRETS.with_borrow_mut(|secrets| {
+            secrets.insert(
+                SecretKey::PayeeMasterKey.as_str().to_string(),
+                Secret {
+                    value: vec![7; 32],
+                    version: 1,
+                    rotated_at: 0,
+                },
+            )
+        });
+        let mut order = test_order();
+        let provider = order.offramper_providers[&PaymentProviderType::PayPal].clone();
+
+        order.seal_payee().unwrap();
+        assert_ne!(
+            order.offramper_providers[&PaymentProviderType::PayPal],
+            provider
+        );
+        assert_eq!(
+            order.payee_account(&PaymentProviderType::PayPal).unwrap(),
+            provider
+        );
+
+        let sealed = order.payee.clone().unwrap();
+        order.reseal_payee().unwrap();
+        let resealed = order.payee.clone().unwrap();
+        assert_eq!(resealed.epoch, sealed.epoch + 1);
+        assert_ne!(resealed.ciphertext, sealed.ciphertext);
+        assert_eq!(
+            order.payee_account(&PaymentProviderType::PayPal).unwrap(),
+            provider
+        );
+
+        // sealing sealed details moves them to the next epoch as well
+        order.seal_payee().unwrap();
+        assert_eq!(order.payee.as_ref().unwrap().epoch, resealed.epoch + 1);
+        assert_eq!(
+            order.payee_account(&PaymentProviderType::PayPal).unwrap(),
+            provider
+        );
+
+        // details of another epoch do not open under this one
+        order.payee = Some(crate::types::orders::EncryptedPayee {
+            epoch: resealed.epoch,
+            ciphertext: sealed.ciphertext,
+        });
+        assert!(order.payee_providers().is_err());
+    }
+
+    #[test]
+    fn test_onramper_pays_from_a_saved_account() {
+        use crate::types::orders::Onramper;
+
+        let login_address = LoginAddress::ICP {
+            principal_id: "2chl6-4hpzw-vqaaa-aaaaa-c".to_string(),
+        };
+        let mut user = User::new(UserType::Onramper, login_address, None).unwrap();
+        let provider = PaymentProvider::PayPal {
+            id: "payer@example.com".to_string(),
+        };
+        let account_id = user
+            .add_payment_account(provider.clone(), "main".to_string())
+            .unwrap();
+        let onramper = Onramper::new(
+            user.id,
+            provider.clone(),
+            TransactionAddress {
+                address_type: AddressType::ICP,
+                address: "2chl6-4hpzw-vqaaa-aaaaa-c".to_string(),
+            },
+        );
+        assert_eq!(onramper.payment_account(&user).unwrap(), &provider);
+
+        user.remove_payment_account(&account_id).unwrap();
+        assert!(onramper.payment_account(&user).is_err());
+    }
 }
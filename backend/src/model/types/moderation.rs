@@ -0,0 +1,123 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_stable_structures::{storable::Bound, Storable};
+
+use crate::errors::{Result, UserError};
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default, PartialEq)]
+pub enum ModerationStatus {
+    #[default]
+    Active,
+    Suspended {
+        reason: String,
+        by: Principal,
+        until: u64, // nanoseconds
+    },
+    Banned {
+        reason: String,
+        by: Principal,
+    },
+}
+
+impl ModerationStatus {
+    /// Reasons are kept on the user record, which has a bounded size.
+    pub const MAX_REASON_LEN: usize = 200;
+
+    /// Suspensions lapse on their own once `until` has passed.
+    pub fn check_active(&self, now: u64) -> Result<()> {
+        match self {
+            ModerationStatus::Active => Ok(()),
+            ModerationStatus::Suspended { until, reason, .. } if now < *until => {
+                Err(UserError::UserSuspended {
+                    until: *until,
+                    reason: reason.clone(),
+                }
+                .into())
+            }
+            ModerationStatus::Suspended { .. } => Ok(()),
+            ModerationStatus::Banned { reason, .. } => Err(UserError::UserBannedByAdmin {
+                reason: reason.clone(),
+            }
+            .into()),
+        }
+    }
+
+    pub fn is_active(&self, now: u64) -> bool {
+        self.check_active(now).is_ok()
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum ModerationAction {
+    Suspend { until: u64 },
+    Ban,
+    Reinstate,
+    AppealApproved,
+    AppealRejected,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct AuditEntry {
+    pub user_id: u64,
+    pub action: ModerationAction,
+    pub reason: String,
+    pub by: Principal,
+    pub timestamp: u64,
+}
+
+impl Storable for AuditEntry {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum AppealStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Appeal {
+    pub user_id: u64,
+    pub message: String,
+    pub submitted_at: u64,
+    pub status: AppealStatus,
+    pub reviewed_by: Option<Principal>,
+    pub review_note: Option<String>,
+}
+
+impl Appeal {
+    pub const MAX_MESSAGE_LEN: usize = 2000;
+
+    pub fn new(user_id: u64, message: String) -> Self {
+        Appeal {
+            user_id,
+            message,
+            submitted_at: ic_cdk::api::time(),
+            status: AppealStatus::Pending,
+            reviewed_by: None,
+            review_note: None,
+        }
+    }
+}
+
+impl Storable for Appeal {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
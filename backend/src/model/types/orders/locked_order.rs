@@ -1,8 +1,9 @@
 use candid::{CandidType, Deserialize};
 
 use crate::{
+    errors::{OrderError, Result},
     model::memory::heap,
-    types::{Blockchain, PaymentProvider, TransactionAddress},
+    types::{user::User, Blockchain, PaymentProvider, TransactionAddress},
 };
 
 use super::order::Order;
@@ -31,6 +32,19 @@ impl Onramper {
             address,
         }
     }
+
+    /// The account the onramper pays from, as long as it is still one of their saved accounts.
+    pub fn payment_account(&self, user: &User) -> Result<&PaymentProvider> {
+        let account_id = self.provider.account_id();
+        if !user
+            .payment_providers
+            .iter()
+            .any(|account| account.id == account_id)
+        {
+            return Err(OrderError::InvalidOnramperProvider.into());
+        }
+        Ok(&self.provider)
+    }
 }
 
 #[derive(CandidType, Deserialize, Clone)]
@@ -55,6 +69,7 @@ pub struct LockedOrder {
     pub revolut_consent: Option<RevolutConsent>,
     pub payment_id: Option<String>,
     pub payment_done: bool,
+    pub paid_at: Option<u64>,
     pub uncommited: bool,
 }
 
@@ -87,12 +102,16 @@ pub struct CompletedOrder {
     pub offramper_fee: u64,
     pub blockchain: Blockchain,
     pub completed_at: u64,
+    pub onramper_user_id: Option<u64>, // unset for orders completed before it was recorded
+    pub offramper_user_id: Option<u64>,
 }
 
 impl From<LockedOrder> for CompletedOrder {
     fn from(locked_order: LockedOrder) -> Self {
         let base = locked_order.base;
         CompletedOrder {
+            onramper_user_id: Some(locked_order.onramper.user_id),
+            offramper_user_id: Some(base.offramper_user_id),
             onramper: locked_order.onramper.address,
             offramper: base.offramper_address,
             price: locked_order.price,
@@ -3,8 +3,14 @@ mod filter;
 mod locked_order;
 mod order;
 mod order_state;
+mod payee;
+mod quote;
+mod view;
 
 pub use filter::*;
 pub use locked_order::*;
 pub use order::*;
 pub use order_state::*;
+pub use payee::*;
+pub use quote::*;
+pub use view::*;
@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use candid::{CandidType, Deserialize};
 
 use super::locked_order::{LockedOrder, Onramper, RevolutConsent};
+use super::payee::EncryptedPayee;
 use crate::{
     errors::{OrderError, Result, SystemError},
     model::{
@@ -19,9 +20,11 @@ pub struct Order {
     pub currency: String,
     pub offramper_user_id: u64,
     pub offramper_address: TransactionAddress,
-    pub offramper_providers: HashMap<PaymentProviderType, PaymentProvider>,
+    pub offramper_providers: HashMap<PaymentProviderType, PaymentProvider>, // account ids redacted when `payee` is set
+    pub offramper_accounts: Option<HashMap<PaymentProviderType, String>>, // saved payment account ids, unset on older orders
     pub crypto: Crypto,
     pub processing: bool,
+    pub payee: Option<EncryptedPayee>,
 }
 
 #[derive(CandidType, Deserialize, Clone)]
@@ -57,17 +60,25 @@ impl Order {
             }
         }
 
+        let offramper_accounts = offramper_providers
+            .iter()
+            .map(|(provider_type, provider)| (provider_type.clone(), provider.account_id()))
+            .collect();
+
         let order_id = heap::generate_order_id();
-        let order = Order {
+        let mut order = Order {
             id: order_id,
             currency,
             created_at: ic_cdk::api::time(),
             offramper_user_id,
             offramper_address,
             offramper_providers,
+            offramper_accounts: Some(offramper_accounts),
             crypto: Crypto::new(blockchain, token, crypto_amount, crypto_fee),
             processing: false,
+            payee: None,
         };
+        order.seal_payee()?;
         ic_cdk::println!("[new order] order = {:?}", order);
 
         Ok(order)
@@ -125,6 +136,7 @@ impl Order {
 
         let mut base_order = self.clone();
         base_order.unset_processing();
+        base_order.reseal_payee()?;
 
         Ok(LockedOrder {
             base: base_order,
@@ -134,6 +146,7 @@ impl Order {
             onramper: Onramper::new(onramper_user_id, onramper_provider, onramper_address),
             revolut_consent,
             payment_done: false,
+            paid_at: None,
             payment_id: None,
             uncommited: false,
         })
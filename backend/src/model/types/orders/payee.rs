@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ic_cdk::api::management_canister::bitcoin::BitcoinNetwork;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    errors::{OrderError, Result, SystemError},
+    management::random,
+    model::memory::stable::secrets,
+    types::{btc, secrets::SecretKey, PaymentProvider, PaymentProviderType},
+};
+
+use super::Order;
+
+const PAYEE_KEY_DOMAIN: &[u8] = b"icramp-payee-v1";
+
+/// Offramper payment identifiers, encrypted under a key derived for the order and `epoch`.
+///
+/// Keys are derived from a master key held by the canister, which only stands in for a vetKD
+/// derivation keyed by order id and epoch: anyone able to read the canister state could derive
+/// them. The local key is therefore only used in tests and on a local replica. The epoch is
+/// bumped whenever the details are sealed again, so that every ciphertext is under a fresh
+/// key, which makes the fixed nonce safe.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct EncryptedPayee {
+    pub epoch: u64,
+    pub ciphertext: Vec<u8>,
+}
+
+/// Whether payee keys may be derived from the local master key, which is only the case in
+/// tests and on a local replica, recognised by its regtest Bitcoin network.
+pub fn local_payee_key_allowed() -> bool {
+    cfg!(test) || matches!(btc::get_network(), Ok(BitcoinNetwork::Regtest))
+}
+
+fn ensure_local_payee_key_allowed() {
+    if !local_payee_key_allowed() {
+        ic_cdk::trap("Payee keys must be derived through vetKD outside of a local replica");
+    }
+}
+
+/// Creates the canister master key on first use. Traps outside of tests and local replicas.
+pub async fn ensure_payee_master_key() -> Result<()> {
+    ensure_local_payee_key_allowed();
+    if secrets::contains_secret(SecretKey::PayeeMasterKey) {
+        return Ok(());
+    }
+
+    let key = random::get_random_bytes().await?;
+    // another call may have created the key while awaiting
+    if !secrets::contains_secret(SecretKey::PayeeMasterKey) {
+        secrets::rotate_secret(SecretKey::PayeeMasterKey, key.to_vec());
+    }
+    Ok(())
+}
+
+fn payee_cipher(order_id: u64, epoch: u64) -> Result<Aes256Gcm> {
+    ensure_local_payee_key_allowed();
+    let master_key = secrets::get_secret(SecretKey::PayeeMasterKey)?;
+    let key = Sha256::new()
+        .chain_update(PAYEE_KEY_DOMAIN)
+        .chain_update(master_key)
+        .chain_update(order_id.to_be_bytes())
+        .chain_update(epoch.to_be_bytes())
+        .finalize();
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+}
+
+fn associated_data(order_id: u64, epoch: u64) -> Vec<u8> {
+    [order_id.to_be_bytes(), epoch.to_be_bytes()].concat()
+}
+
+impl EncryptedPayee {
+    fn seal(
+        order_id: u64,
+        epoch: u64,
+        providers: &HashMap<PaymentProviderType, PaymentProvider>,
+    ) -> Result<Self> {
+        let plaintext =
+            Encode!(providers).map_err(|e| SystemError::InternalError(e.to_string()))?;
+        let ciphertext = payee_cipher(order_id, epoch)?
+            .encrypt(
+                Nonce::from_slice(&[0u8; 12]),
+                Payload {
+                    msg: &plaintext,
+                    aad: &associated_data(order_id, epoch),
+                },
+            )
+            .map_err(|_| SystemError::InternalError("Failed to encrypt payee".to_string()))?;
+
+        Ok(EncryptedPayee { epoch, ciphertext })
+    }
+
+    fn open(&self, order_id: u64) -> Result<HashMap<PaymentProviderType, PaymentProvider>> {
+        let plaintext = payee_cipher(order_id, self.epoch)?
+            .decrypt(
+                Nonce::from_slice(&[0u8; 12]),
+                Payload {
+                    msg: &self.ciphertext,
+                    aad: &associated_data(order_id, self.epoch),
+                },
+            )
+            .map_err(|_| SystemError::InternalError("Failed to decrypt payee".to_string()))?;
+
+        Decode!(&plaintext, HashMap<PaymentProviderType, PaymentProvider>)
+            .map_err(|e| SystemError::InternalError(e.to_string()).into())
+    }
+}
+
+impl Order {
+    /// Encrypts the payee details and keeps only the provider types in clear. Details that are
+    /// already sealed are sealed again under the next epoch.
+    pub fn seal_payee(&mut self) -> Result<()> {
+        if self.payee.is_some() {
+            return self.reseal_payee();
+        }
+
+        self.payee = Some(EncryptedPayee::seal(self.id, 0, &self.offramper_providers)?);
+        self.offramper_providers
+            .values_mut()
+            .for_each(PaymentProvider::redact);
+        Ok(())
+    }
+
+    /// Re-encrypts the payee details under the next epoch.
+    pub fn reseal_payee(&mut self) -> Result<()> {
+        let Some(payee) = &self.payee else {
+            return Ok(());
+        };
+
+        let providers = payee.open(self.id)?;
+        self.payee = Some(EncryptedPayee::seal(self.id, payee.epoch + 1, &providers)?);
+        Ok(())
+    }
+
+    /// The payee account of `provider_type`, checked against the account saved with the order.
+    pub fn payee_account(&self, provider_type: &PaymentProviderType) -> Result<PaymentProvider> {
+        let provider = self
+            .payee_providers()?
+            .remove(provider_type)
+            .ok_or(OrderError::InvalidOfframperProvider)?;
+
+        match &self.offramper_accounts {
+            Some(accounts) if accounts.get(provider_type) != Some(&provider.account_id()) => {
+                Err(OrderError::InvalidOfframperProvider.into())
+            }
+            _ => Ok(provider),
+        }
+    }
+
+    /// Payee details for payment handling. Orders created before encryption hold them in clear.
+    pub fn payee_providers(&self) -> Result<HashMap<PaymentProviderType, PaymentProvider>> {
+        match &self.payee {
+            Some(payee) => payee.open(self.id),
+            None => Ok(self.offramper_providers.clone()),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use candid::{CandidType, Deserialize};
+
+use crate::{
+    errors::{OrderError, Result},
+    types::PaymentProviderType,
+};
+
+use super::Order;
+
+pub(crate) const QUOTE_DURATION_SECONDS: u64 = 120; // 2 min
+pub(crate) const DEFAULT_MAX_SLIPPAGE_BPS: u32 = 100; // 1%
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct OrderQuote {
+    pub id: String,
+    pub order_id: u64,
+    pub user_id: u64,
+    pub provider_type: PaymentProviderType,
+    pub currency: String,
+    pub price: u64,
+    pub offramper_fee: u64,
+    pub crypto_amount: u128,
+    pub crypto_fee: u128,
+    pub net_crypto_amount: u128,
+    pub created_at: u64,
+    pub expires_at: u64,
+}
+
+impl OrderQuote {
+    pub fn new(
+        id: String,
+        order: &Order,
+        user_id: u64,
+        provider_type: PaymentProviderType,
+        price: u64,
+        offramper_fee: u64,
+    ) -> Self {
+        let now = ic_cdk::api::time();
+        OrderQuote {
+            id,
+            order_id: order.id,
+            user_id,
+            provider_type,
+            currency: order.currency.clone(),
+            price,
+            offramper_fee,
+            crypto_amount: order.crypto.amount,
+            crypto_fee: order.crypto.fee,
+            net_crypto_amount: order.crypto.amount.saturating_sub(order.crypto.fee),
+            created_at: now,
+            expires_at: now + QUOTE_DURATION_SECONDS * 1_000_000_000,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(ic_cdk::api::time())
+    }
+
+    pub fn is_expired_at(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Checks that the quote was issued to this user for this exact order and provider,
+    /// and that the order has not been topped up since the quote was given.
+    pub fn validate_for(
+        &self,
+        order: &Order,
+        user_id: u64,
+        provider_type: &PaymentProviderType,
+    ) -> Result<()> {
+        if self.order_id != order.id
+            || self.user_id != user_id
+            || self.provider_type != *provider_type
+            || self.currency != order.currency
+            || self.crypto_amount != order.crypto.amount
+            || self.crypto_fee != order.crypto.fee
+        {
+            return Err(OrderError::InvalidQuote.into());
+        }
+        if self.is_expired() {
+            return Err(OrderError::QuoteExpired.into());
+        }
+        Ok(())
+    }
+
+    /// Fails if the current market price deviates from the quoted price by more
+    /// than `max_slippage_bps` basis points.
+    pub fn check_slippage(&self, current_price: u64, max_slippage_bps: u32) -> Result<()> {
+        if self.price == 0 {
+            return Err(OrderError::InvalidQuote.into());
+        }
+
+        let deviation = self.price.abs_diff(current_price) as u128;
+        let deviation_bps = deviation * 10_000 / self.price as u128;
+        if deviation_bps > max_slippage_bps as u128 {
+            return Err(OrderError::PriceSlippageExceeded {
+                quoted_price: self.price,
+                current_price,
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
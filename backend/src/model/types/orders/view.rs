@@ -0,0 +1,97 @@
+use crate::types::{PaymentProviderType, TransactionAddress};
+
+use super::{CompletedOrder, LockedOrder, Order, OrderState};
+
+/// Who an order is returned to. Users see the orders they are a party of in full.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OrderViewer {
+    Public,
+    User(u64),
+    Admin,
+}
+
+impl OrderViewer {
+    fn is_user(&self, user_id: u64) -> bool {
+        *self == OrderViewer::User(user_id)
+    }
+
+    /// Whether the viewer sees the unredacted details of `user_id`.
+    pub fn sees_details_of(&self, user_id: u64) -> bool {
+        *self == OrderViewer::Admin || self.is_user(user_id)
+    }
+}
+
+fn redact_address(address: &mut TransactionAddress) {
+    address.address.clear();
+}
+
+impl Order {
+    /// Redacts the payee details, except for the provider of type `keep`.
+    fn redact_providers(&mut self, keep: Option<&PaymentProviderType>) {
+        for (provider_type, provider) in self.offramper_providers.iter_mut() {
+            if keep != Some(provider_type) {
+                provider.redact();
+            }
+        }
+    }
+
+    fn redact(&mut self) {
+        self.redact_providers(None);
+        self.offramper_accounts = None;
+        redact_address(&mut self.offramper_address);
+    }
+}
+
+impl LockedOrder {
+    fn redact(&mut self) {
+        self.base.redact();
+        self.onramper.provider.redact();
+        redact_address(&mut self.onramper.address);
+        self.revolut_consent = None;
+        self.payment_id = None;
+    }
+}
+
+impl CompletedOrder {
+    fn is_party(&self, viewer: &OrderViewer) -> bool {
+        [self.onramper_user_id, self.offramper_user_id]
+            .into_iter()
+            .flatten()
+            .any(|user_id| viewer.is_user(user_id))
+    }
+}
+
+impl OrderState {
+    /// Returns the order with the details `viewer` is not allowed to see redacted.
+    pub fn view(mut self, viewer: &OrderViewer) -> OrderState {
+        if *viewer == OrderViewer::Admin {
+            return self;
+        }
+
+        match &mut self {
+            OrderState::Created(order) => {
+                if !viewer.is_user(order.offramper_user_id) {
+                    order.redact();
+                }
+            }
+            OrderState::Locked(order) => {
+                if viewer.is_user(order.onramper.user_id) {
+                    // only orders created before payee encryption still hold the account in clear,
+                    // of which the onramper needs the one they are paying to
+                    let provider_type = order.onramper.provider.provider_type();
+                    order.base.redact_providers(Some(&provider_type));
+                } else if !viewer.is_user(order.base.offramper_user_id) {
+                    order.redact();
+                }
+            }
+            OrderState::Completed(order) => {
+                if !order.is_party(viewer) {
+                    redact_address(&mut order.onramper);
+                    redact_address(&mut order.offramper);
+                }
+            }
+            OrderState::Cancelled(_) => (),
+        }
+        self
+    }
+}
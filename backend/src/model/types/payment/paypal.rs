@@ -1,16 +1,27 @@
+use core::fmt;
+
 use candid::{CandidType, Deserialize};
 
 use crate::model::memory::heap::{mutate_state, read_state};
 
-#[derive(Clone, Debug, CandidType, Deserialize)]
+#[derive(Clone, CandidType, Deserialize)]
 pub struct PayPalState {
     pub access_token: Option<String>,
     pub token_expiration: Option<u64>,
     pub client_id: String,
-    pub client_secret: String,
+    pub client_secret: Option<String>, // only set in states saved before the secret store
     pub api_url: String,
 }
 
+impl fmt::Debug for PayPalState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PayPalState")
+            .field("client_id", &self.client_id)
+            .field("api_url", &self.api_url)
+            .finish()
+    }
+}
+
 pub fn get_paypal_token() -> Option<(String, u64)> {
     read_state(|s| {
         if let (Some(token), Some(expiration)) =
@@ -1,9 +1,7 @@
-use std::{
-    collections::HashMap,
-    hash::{Hash, Hasher},
-};
+use std::collections::HashMap;
 
 use candid::{CandidType, Deserialize};
+use sha2::{Digest, Sha256};
 
 use crate::errors::{Result, SystemError};
 
@@ -13,7 +11,7 @@ pub enum PaymentProviderType {
     Revolut,
 }
 
-#[derive(CandidType, Deserialize, Clone, Debug, Eq)]
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum PaymentProvider {
     PayPal {
         id: String,
@@ -25,19 +23,31 @@ pub enum PaymentProvider {
     },
 }
 
-impl PartialEq for PaymentProvider {
-    fn eq(&self, other: &Self) -> bool {
-        self.provider_type() == other.provider_type()
+impl PaymentProvider {
+    const MAX_DETAIL_LENGTH: usize = 128;
+
+    /// Stable id of the account, derived from its details. The Revolut account holder name is
+    /// left out: it names the same account, so saving it again with another name updates the
+    /// saved account instead of adding one.
+    pub fn account_id(&self) -> String {
+        let details = match self {
+            PaymentProvider::PayPal { id } => format!("paypal:{}", id),
+            PaymentProvider::Revolut { scheme, id, .. } => format!("revolut:{}:{}", scheme, id),
+        };
+        hex::encode(&Sha256::digest(details.as_bytes())[..8])
     }
-}
 
-impl Hash for PaymentProvider {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.provider_type().hash(state);
+    /// Clears the account details, keeping the provider type.
+    pub fn redact(&mut self) {
+        match self {
+            PaymentProvider::PayPal { id } => id.clear(),
+            PaymentProvider::Revolut { id, name, .. } => {
+                id.clear();
+                *name = None;
+            }
+        }
     }
-}
 
-impl PaymentProvider {
     pub fn provider_type(&self) -> PaymentProviderType {
         match self {
             PaymentProvider::PayPal { .. } => PaymentProviderType::PayPal,
@@ -46,6 +56,23 @@ impl PaymentProvider {
     }
 
     pub fn validate(&self) -> Result<()> {
+        let details = match self {
+            PaymentProvider::PayPal { id } => vec![id],
+            PaymentProvider::Revolut { scheme, id, name } => {
+                [scheme, id].into_iter().chain(name.as_ref()).collect()
+            }
+        };
+        if details
+            .iter()
+            .any(|detail| detail.len() > Self::MAX_DETAIL_LENGTH)
+        {
+            return Err(SystemError::InvalidInput(format!(
+                "Payment details are longer than {} bytes",
+                Self::MAX_DETAIL_LENGTH
+            ))
+            .into());
+        }
+
         match self {
             PaymentProvider::PayPal { id } => {
                 if id.is_empty() {
@@ -64,6 +91,24 @@ impl PaymentProvider {
     }
 }
 
+/// Payment provider account saved by a user. A user can hold several accounts per provider.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct PaymentAccount {
+    pub id: String,
+    pub label: String,
+    pub provider: PaymentProvider,
+}
+
+impl PaymentAccount {
+    pub fn new(provider: PaymentProvider, label: String) -> Self {
+        PaymentAccount {
+            id: provider.account_id(),
+            label,
+            provider,
+        }
+    }
+}
+
 pub fn contains_provider_type(
     provider: &PaymentProvider,
     providers: &HashMap<PaymentProviderType, PaymentProvider>,
@@ -11,7 +11,7 @@ pub struct RevolutState {
     pub client_id: String,
     pub api_url: String,
     pub proxy_url: String,
-    pub private_key_der: Vec<u8>,
+    pub private_key_der: Option<Vec<u8>>, // only set in states saved before the secret store
     pub kid: String,
     pub tan: String,
 }
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use candid::{CandidType, Deserialize, Principal};
+
+/// Groups of update endpoints that share a rate limit.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitClass {
+    Auth,    // registration, auth messages, login and password resets
+    Lock,    // quotes and order locking, which spend cycles on outcalls
+    Order,   // order creation, cancellation and top ups
+    Payment, // payment verification
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    Principal(Principal),
+    Anonymous, // shared by all anonymous callers
+    User(u64),
+}
+
+/// Token bucket holding up to `capacity` calls, refilled linearly over `window_secs`.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug)]
+pub struct BucketConfig {
+    pub capacity: u32,
+    pub window_secs: u64,
+}
+
+impl BucketConfig {
+    // anonymous callers share one bucket, sized for many of them
+    const ANONYMOUS_SCALE: u32 = 50;
+
+    /// The configuration of the bucket behind `key`.
+    pub fn for_key(&self, key: &RateLimitKey) -> BucketConfig {
+        match key {
+            RateLimitKey::Anonymous => BucketConfig {
+                capacity: self.capacity.saturating_mul(Self::ANONYMOUS_SCALE),
+                window_secs: self.window_secs,
+            },
+            _ => *self,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub limits: HashMap<RateLimitClass, BucketConfig>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let bucket = |capacity, window_secs| BucketConfig {
+            capacity,
+            window_secs,
+        };
+
+        RateLimitConfig {
+            enabled: true,
+            limits: HashMap::from([
+                (RateLimitClass::Auth, bucket(10, 60)),
+                (RateLimitClass::Lock, bucket(5, 300)),
+                (RateLimitClass::Order, bucket(20, 60)),
+                (RateLimitClass::Payment, bucket(10, 60)),
+            ]),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TokenBucket {
+    pub tokens: f64,
+    pub last_refill: u64, // nanoseconds
+}
+
+impl TokenBucket {
+    pub fn new(config: &BucketConfig, now: u64) -> Self {
+        TokenBucket {
+            tokens: config.capacity as f64,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, config: &BucketConfig, now: u64) {
+        let elapsed_secs = now.saturating_sub(self.last_refill) as f64 / 1_000_000_000.;
+        let rate = config.capacity as f64 / config.window_secs.max(1) as f64;
+        self.tokens = (self.tokens + elapsed_secs * rate).min(config.capacity as f64);
+        self.last_refill = now;
+    }
+
+    /// Takes one token, or returns the seconds until one is available.
+    pub fn try_take(&mut self, config: &BucketConfig, now: u64) -> Result<(), u64> {
+        self.refill(config, now);
+        if self.tokens >= 1. {
+            self.tokens -= 1.;
+            return Ok(());
+        }
+
+        let rate = config.capacity as f64 / config.window_secs.max(1) as f64;
+        Err(((1. - self.tokens) / rate).ceil() as u64)
+    }
+
+    pub fn is_full(&self, config: &BucketConfig, now: u64) -> bool {
+        let mut bucket = self.clone();
+        bucket.refill(config, now);
+        bucket.tokens >= config.capacity as f64
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RateLimitEntry {
+    pub key: RateLimitKey,
+    pub class: RateLimitClass,
+    pub bucket: TokenBucket,
+}
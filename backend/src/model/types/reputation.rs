@@ -0,0 +1,159 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ic_stable_structures::{storable::Bound, Storable};
+
+use super::{limits::VerificationLevel, user::UserType};
+
+const MAX_TIMING_SAMPLES: usize = 50;
+
+/// Trade statistics of a user in one role.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct Reputation {
+    pub rating_count: u64,
+    pub rating_sum: u64,
+    pub completed_trades: u64,
+    pub lock_expiries: u64, // locks that expired without payment, as onramper
+    pub disputes_won: u64,
+    pub disputes_lost: u64,
+    lock_to_payment_secs: Vec<u64>,    // latest samples, as onramper
+    payment_to_release_secs: Vec<u64>, // latest samples, as offramper
+}
+
+/// Trade statistics of a user, kept apart per role so that trades in one role do not vouch
+/// for the other.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct UserReputations(pub HashMap<UserType, Reputation>);
+
+impl Reputation {
+    pub fn add_rating(&mut self, stars: u8) {
+        self.rating_count += 1;
+        self.rating_sum += stars as u64;
+    }
+
+    pub fn record_completed_trade(&mut self) {
+        self.completed_trades += 1;
+    }
+
+    pub fn record_lock_expiry(&mut self) {
+        self.lock_expiries += 1;
+    }
+
+    pub fn record_lock_to_payment(&mut self, secs: u64) {
+        push_sample(&mut self.lock_to_payment_secs, secs);
+    }
+
+    pub fn record_payment_to_release(&mut self, secs: u64) {
+        push_sample(&mut self.payment_to_release_secs, secs);
+    }
+
+    pub fn record_dispute(&mut self, won: bool) {
+        if won {
+            self.disputes_won += 1;
+        } else {
+            self.disputes_lost += 1;
+        }
+    }
+
+    pub fn average_rating(&self) -> Option<f64> {
+        (self.rating_count > 0).then(|| self.rating_sum as f64 / self.rating_count as f64)
+    }
+
+    /// Completed trades over completed trades plus expired locks, in basis points.
+    pub fn completion_rate_bps(&self) -> Option<u32> {
+        let total = self.completed_trades + self.lock_expiries;
+        (total > 0).then(|| (self.completed_trades * 10_000 / total) as u32)
+    }
+
+    pub fn median_lock_to_payment_secs(&self) -> Option<u64> {
+        median(&self.lock_to_payment_secs)
+    }
+
+    pub fn median_payment_to_release_secs(&self) -> Option<u64> {
+        median(&self.payment_to_release_secs)
+    }
+}
+
+fn push_sample(samples: &mut Vec<u64>, value: u64) {
+    if samples.len() >= MAX_TIMING_SAMPLES {
+        samples.remove(0);
+    }
+    samples.push(value);
+}
+
+fn median(samples: &[u64]) -> Option<u64> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        Some((sorted[mid - 1] + sorted[mid]) / 2)
+    } else {
+        Some(sorted[mid])
+    }
+}
+
+impl Storable for UserReputations {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        // records saved before reputations were kept per role cannot be split between roles,
+        // so they are kept as onramper statistics
+        Decode!(bytes.as_ref(), Self)
+            .or_else(|_| {
+                Decode!(bytes.as_ref(), Reputation).map(|reputation| {
+                    UserReputations(HashMap::from([(UserType::Onramper, reputation)]))
+                })
+            })
+            .unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Public reputation profile, without login, password or payment details.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct UserReputation {
+    pub user_id: u64,
+    pub roles: HashSet<UserType>,
+    pub verification_level: VerificationLevel,
+    pub scores: HashMap<UserType, i32>,
+    pub reputations: HashMap<UserType, RoleReputation>,
+}
+
+/// Public trade statistics of a user in one role.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RoleReputation {
+    pub rating_count: u64,
+    pub average_rating: Option<f64>,
+    pub completed_trades: u64,
+    pub completion_rate_bps: Option<u32>,
+    pub lock_expiries: u64,
+    pub median_lock_to_payment_secs: Option<u64>,
+    pub median_payment_to_release_secs: Option<u64>,
+    pub disputes_won: u64,
+    pub disputes_lost: u64,
+}
+
+impl From<&Reputation> for RoleReputation {
+    fn from(reputation: &Reputation) -> Self {
+        RoleReputation {
+            rating_count: reputation.rating_count,
+            average_rating: reputation.average_rating(),
+            completed_trades: reputation.completed_trades,
+            completion_rate_bps: reputation.completion_rate_bps(),
+            lock_expiries: reputation.lock_expiries,
+            median_lock_to_payment_secs: reputation.median_lock_to_payment_secs(),
+            median_payment_to_release_secs: reputation.median_payment_to_release_secs(),
+            disputes_won: reputation.disputes_won,
+            disputes_lost: reputation.disputes_lost,
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use std::{borrow::Cow, fmt};
+
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ic_stable_structures::{storable::Bound, Storable};
+
+/// Credentials kept out of `State`.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SecretKey {
+    PaypalClientSecret,
+    RevolutPrivateKey, // PEM encoded PKCS#8 key used to sign JWS requests
+    PayeeMasterKey,    // generated by the canister, never rotated as it encrypts open orders
+    MailApiKey,
+}
+
+impl SecretKey {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SecretKey::PaypalClientSecret => "paypal_client_secret",
+            SecretKey::RevolutPrivateKey => "revolut_private_key",
+            SecretKey::PayeeMasterKey => "payee_master_key",
+            SecretKey::MailApiKey => "mail_api_key",
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct Secret {
+    pub value: Vec<u8>,
+    pub version: u32,
+    pub rotated_at: u64,
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Secret")
+            .field("value", &"[REDACTED]")
+            .field("version", &self.version)
+            .field("rotated_at", &self.rotated_at)
+            .finish()
+    }
+}
+
+impl Storable for Secret {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Secret metadata, safe to return from queries.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SecretInfo {
+    pub key: SecretKey,
+    pub version: u32,
+    pub rotated_at: u64,
+}
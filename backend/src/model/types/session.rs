@@ -1,33 +1,144 @@
-use candid::{CandidType, Deserialize};
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_stable_structures::{storable::Bound, Storable};
+use sha2::{Digest, Sha256};
 
 use crate::{
     management::random,
-    model::errors::{Result, UserError},
+    model::errors::{Result, SystemError, UserError},
 };
 
+const MAX_USER_SESSIONS_SIZE: u32 = 4096;
+
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub struct Session {
+    pub id: String,
+    pub name: String, // e.g. device name
     pub token: String,
-    pub expires_at: u64, // nanoseconds
+    pub created_at: u64,              // nanoseconds
+    pub expires_at: u64,              // nanoseconds
+    pub principal: Option<Principal>, // caller the session is bound to
 }
 
 impl Session {
     pub(crate) const EXPIRATION_SECS: u64 = 43200; // 12h
+    pub(crate) const MAX_LIFETIME_SECS: u64 = 7 * 24 * 60 * 60; // 7 days
+    pub(crate) const MAX_SESSIONS: usize = 10;
+    pub(crate) const MAX_NAME_LENGTH: usize = 64; // bytes, keeps `UserSessions` within its bound
+
+    pub async fn new(name: String, principal: Option<Principal>) -> Result<Self> {
+        if name.len() > Self::MAX_NAME_LENGTH {
+            return Err(SystemError::InvalidInput(format!(
+                "Session name is longer than {} bytes",
+                Self::MAX_NAME_LENGTH
+            ))
+            .into());
+        }
 
-    pub async fn new() -> Result<Self> {
+        let token = random::generate_token().await?;
+        let now = ic_cdk::api::time();
         Ok(Session {
-            token: random::generate_token().await?,
-            expires_at: ic_cdk::api::time() + Self::EXPIRATION_SECS * 1_000_000_000,
+            // derived from the token, so that sessions can be listed without exposing it
+            id: hex::encode(&Sha256::digest(token.as_bytes())[..8]),
+            name,
+            token,
+            created_at: now,
+            expires_at: now + Self::EXPIRATION_SECS * 1_000_000_000,
+            principal,
         })
     }
 
+    pub fn is_expired(&self) -> bool {
+        ic_cdk::api::time() >= self.expires_at
+    }
+
     pub fn validate(&self, provided_token: &str) -> Result<()> {
         if self.token != provided_token {
             return Err(UserError::TokenInvalid.into());
         }
-        if ic_cdk::api::time() >= self.expires_at {
+        if self.is_expired() {
             return Err(UserError::TokenExpired.into());
         }
+        if let Some(principal) = self.principal {
+            if ic_cdk::caller() != principal {
+                return Err(UserError::SessionPrincipalMismatch.into());
+            }
+        }
         Ok(())
     }
+
+    /// Slides the expiry forward, up to the maximum lifetime of the session.
+    pub fn refresh(&mut self) {
+        let max_expires_at = self.created_at + Self::MAX_LIFETIME_SECS * 1_000_000_000;
+        self.expires_at =
+            (ic_cdk::api::time() + Self::EXPIRATION_SECS * 1_000_000_000).min(max_expires_at);
+    }
+}
+
+/// Session as listed to its user, without the token.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SessionInfo {
+    pub id: String,
+    pub name: String,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub principal: Option<Principal>,
+}
+
+impl From<&Session> for SessionInfo {
+    fn from(session: &Session) -> Self {
+        SessionInfo {
+            id: session.id.clone(),
+            name: session.name.clone(),
+            created_at: session.created_at,
+            expires_at: session.expires_at,
+            principal: session.principal,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct UserSessions(pub Vec<Session>);
+
+impl UserSessions {
+    /// Adds a session, dropping expired ones and evicting the oldest above the cap.
+    pub fn add(&mut self, session: Session) {
+        self.0.retain(|stored| !stored.is_expired());
+        self.0.push(session);
+        if self.0.len() > Session::MAX_SESSIONS {
+            self.0.sort_by_key(|stored| stored.created_at);
+            let excess = self.0.len() - Session::MAX_SESSIONS;
+            self.0.drain(..excess);
+        }
+    }
+
+    pub fn find(&self, token: &str) -> Result<&Session> {
+        self.0
+            .iter()
+            .find(|session| session.token == token)
+            .ok_or_else(|| UserError::SessionNotFound.into())
+    }
+
+    pub fn find_mut(&mut self, token: &str) -> Result<&mut Session> {
+        self.0
+            .iter_mut()
+            .find(|session| session.token == token)
+            .ok_or_else(|| UserError::SessionNotFound.into())
+    }
+}
+
+impl Storable for UserSessions {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: MAX_USER_SESSIONS_SIZE,
+        is_fixed_size: false,
+    };
 }
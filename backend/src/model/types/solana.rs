@@ -0,0 +1,21 @@
+use ed25519_dalek::{Signature, VerifyingKey};
+
+use crate::{
+    errors::{Result, UserError},
+    model::helpers,
+};
+
+/// Verifies a base58 encoded ed25519 `signature` of `message` by the Solana `address`.
+pub fn verify_signature(address: &str, message: &str, signature: &str) -> Result<()> {
+    let public_key = VerifyingKey::from_bytes(&helpers::decode_solana_pubkey(address)?)
+        .map_err(|_| UserError::InvalidSignature)?;
+    let signature_bytes = bs58::decode(signature)
+        .into_vec()
+        .ok()
+        .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+        .ok_or(UserError::InvalidSignature)?;
+
+    public_key
+        .verify_strict(message.as_bytes(), &Signature::from_bytes(&signature_bytes))
+        .map_err(|_| UserError::InvalidSignature.into())
+}
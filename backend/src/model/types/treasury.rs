@@ -0,0 +1,165 @@
+use std::{borrow::Cow, collections::HashSet};
+
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_stable_structures::{storable::Bound, Storable};
+
+use crate::errors::{Result, UserError};
+
+/// Signer set that has to approve treasury movements before they are executed.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TreasuryConfig {
+    pub signers: HashSet<Principal>,
+    pub threshold: u32,
+    pub expiry_secs: u64, // window for gathering approvals, and for executing once unlocked
+    pub timelock_secs: u64, // delay between the last approval and execution
+}
+
+impl Default for TreasuryConfig {
+    fn default() -> Self {
+        TreasuryConfig {
+            signers: HashSet::new(),
+            threshold: 2,
+            expiry_secs: 3 * 24 * 60 * 60, // 3 days
+            timelock_secs: 0,
+        }
+    }
+}
+
+impl TreasuryConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.threshold == 0 || self.threshold as usize > self.signers.len() {
+            return Err(UserError::TreasuryNotConfigured)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum TreasuryAction {
+    CanisterTransfer {
+        ledger_canister: Principal,
+        to_principal: Principal,
+        amount: u128,
+    },
+    EvmTransfer {
+        chain_id: u64,
+        to: String,
+        amount: u128,
+        token: Option<String>,
+        estimated_gas: Option<u64>,
+    },
+    EvmFeeWithdrawal {
+        chain_id: u64,
+        amount: u128,
+        token: Option<String>,
+    },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum ProposalStatus {
+    Pending,
+    Approved { at: u64 },
+    Executing,
+    Executed { at: u64 },
+    Failed { at: u64, error: String },
+    Cancelled { by: Principal },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TreasuryProposal {
+    pub id: u64,
+    pub action: TreasuryAction,
+    pub proposer: Principal,
+    pub approvals: Vec<Principal>,
+    pub threshold: u32,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub executable_at: Option<u64>,
+    pub status: ProposalStatus,
+}
+
+impl TreasuryProposal {
+    pub fn new(
+        id: u64,
+        action: TreasuryAction,
+        proposer: Principal,
+        config: &TreasuryConfig,
+        now: u64,
+    ) -> Self {
+        TreasuryProposal {
+            id,
+            action,
+            proposer,
+            approvals: Vec::new(),
+            threshold: config.threshold,
+            created_at: now,
+            expires_at: now.saturating_add(config.expiry_secs.saturating_mul(1_000_000_000)),
+            executable_at: None,
+            status: ProposalStatus::Pending,
+        }
+    }
+
+    /// Adds the approval of `signer`, moving the proposal to `Approved` once the threshold is met.
+    pub fn approve(&mut self, signer: Principal, timelock_secs: u64, now: u64) -> Result<()> {
+        if self.status != ProposalStatus::Pending {
+            return Err(UserError::ProposalNotPending)?;
+        }
+        if now >= self.expires_at {
+            return Err(UserError::ProposalExpired)?;
+        }
+        if self.approvals.contains(&signer) {
+            return Err(UserError::ProposalAlreadyApproved)?;
+        }
+
+        self.approvals.push(signer);
+        if self.approvals.len() >= self.threshold as usize {
+            self.status = ProposalStatus::Approved { at: now };
+            self.executable_at =
+                Some(now.saturating_add(timelock_secs.saturating_mul(1_000_000_000)));
+        }
+        Ok(())
+    }
+
+    /// Approved proposals can be executed once their timelock has passed, for as long as the
+    /// window they had for gathering approvals, and while enough of their approvers are still
+    /// signers under `config`.
+    pub fn check_executable(&self, config: &TreasuryConfig, now: u64) -> Result<()> {
+        if !matches!(self.status, ProposalStatus::Approved { .. }) {
+            return Err(UserError::ProposalNotApproved)?;
+        }
+        let Some(executable_at) = self.executable_at else {
+            return Err(UserError::ProposalNotApproved)?;
+        };
+        if now < executable_at {
+            return Err(UserError::ProposalTimelocked {
+                until: executable_at,
+            })?;
+        }
+        let window = self.expires_at.saturating_sub(self.created_at);
+        if now >= executable_at.saturating_add(window) {
+            return Err(UserError::ProposalExpired)?;
+        }
+
+        let approvals = self
+            .approvals
+            .iter()
+            .filter(|signer| config.signers.contains(signer))
+            .count();
+        if approvals < self.threshold.max(config.threshold) as usize {
+            return Err(UserError::ProposalNotApproved)?;
+        }
+        Ok(())
+    }
+}
+
+impl Storable for TreasuryProposal {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
@@ -1,4 +1,5 @@
 use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ethers_core::utils::hash_message;
 use ic_stable_structures::{storable::Bound, Storable};
 use std::{
     borrow::Cow,
@@ -6,9 +7,14 @@ use std::{
 };
 
 use super::{
-    common::{LoginAddress, TransactionAddress},
-    session::Session,
-    AuthenticationData, PaymentProvider,
+    btc,
+    common::{AddressType, LoginAddress, TransactionAddress},
+    evm::siwe::SiweMessage,
+    limits::{FiatLimit, VerificationLevel},
+    moderation::ModerationStatus,
+    solana,
+    wallet_auth::WalletAuthMessage,
+    AuthenticationData, EvmSignatureType, PaymentAccount, PaymentProvider,
 };
 use crate::{
     errors::{BlockchainError, Result, SystemError, UserError},
@@ -17,29 +23,46 @@ use crate::{
     model::memory,
 };
 
-const MAX_USER_SIZE: u32 = 1000;
+// Raising this bound is safe on upgrade: `StableBTreeMap::init` loads existing maps as v2,
+// which accept values up to the new size. Lowering it would strand larger users.
+const MAX_USER_SIZE: u32 = 12288;
 
-#[derive(CandidType, Deserialize, Clone, Debug)]
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum UserType {
     Offramper,
     Onramper,
 }
 
+/// Payout address saved by a user. Each address type has one default address.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct UserAddress {
+    pub label: String,
+    pub address: TransactionAddress,
+    pub is_default: bool,
+}
+
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub struct User {
     pub id: u64,
-    pub user_type: UserType,
-    pub payment_providers: HashSet<PaymentProvider>,
-    pub addresses: HashSet<TransactionAddress>,
+    pub roles: HashSet<UserType>,
+    pub payment_providers: Vec<PaymentAccount>,
+    pub addresses: Vec<UserAddress>,
     pub fiat_amounts: HashMap<String, u64>, // offramped or onramped funds
-    pub score: i32,
-    pub login: LoginAddress,
-    pub hashed_password: Option<String>,  // for email login
-    pub evm_auth_message: Option<String>, // for EVM login, unique per session
-    pub session: Option<Session>,
+    pub scores: HashMap<UserType, i32>,     // reputation per role
+    pub logins: HashSet<LoginAddress>,
+    pub passwords: HashMap<String, String>, // email login index key -> password hash
+    pub evm_auth_message: Option<String>, // for wallet login (EVM, Solana, Bitcoin), consumed on login
+    pub verification_level: VerificationLevel,
+    pub moderation: ModerationStatus,
 }
 
 impl User {
+    // per user caps keeping a full user within `MAX_USER_SIZE`
+    pub(crate) const MAX_LOGINS: usize = 5;
+    pub(crate) const MAX_ADDRESSES: usize = 10;
+    pub(crate) const MAX_PAYMENT_ACCOUNTS: usize = 5;
+    pub(crate) const MAX_LABEL_LENGTH: usize = 32;
+
     pub fn new(
         user_type: UserType,
         login_address: LoginAddress,
@@ -47,53 +70,133 @@ impl User {
     ) -> Result<Self> {
         login_address.validate()?;
 
-        let mut addresses = HashSet::new();
+        let mut addresses = Vec::new();
+        let mut passwords = HashMap::new();
         if let LoginAddress::Email { .. } = login_address {
+            passwords.insert(
+                login_address.index_key(),
+                hashed_password.ok_or(UserError::PasswordRequired)?,
+            );
         } else {
-            addresses.insert(login_address.to_transaction_address()?);
+            addresses.push(UserAddress {
+                label: "login".to_string(),
+                address: login_address.to_transaction_address()?,
+                is_default: true,
+            });
         };
 
         Ok(Self {
             id: memory::heap::generate_user_id(),
-            user_type,
-            payment_providers: HashSet::new(),
+            roles: HashSet::from([user_type.clone()]),
+            payment_providers: Vec::new(),
             fiat_amounts: HashMap::new(),
-            score: 1,
-            login: login_address,
-            hashed_password,
+            scores: HashMap::from([(user_type, 1)]),
+            logins: HashSet::from([login_address]),
+            passwords,
             evm_auth_message: None,
             addresses,
-            session: None,
+            verification_level: VerificationLevel::default(),
+            moderation: ModerationStatus::default(),
         })
     }
 
-    pub fn is_offramper(&self) -> Result<()> {
-        match self.user_type {
-            UserType::Offramper => Ok(()),
-            UserType::Onramper => Err(UserError::UserNotOfframper.into()),
+    pub fn validate_role(&self, role: &UserType) -> Result<()> {
+        if self.roles.contains(role) {
+            return Ok(());
+        }
+        match role {
+            UserType::Offramper => Err(UserError::UserNotOfframper.into()),
+            UserType::Onramper => Err(UserError::UserNotOnramper.into()),
+        }
+    }
+
+    pub fn add_role(&mut self, role: UserType) {
+        self.scores.entry(role.clone()).or_insert(1);
+        self.roles.insert(role);
+    }
+
+    /// Removes a role, keeping its reputation in case the role is added back.
+    pub fn remove_role(&mut self, role: &UserType) -> Result<()> {
+        self.validate_role(role)?;
+        if self.roles.len() == 1 {
+            return Err(UserError::CannotRemoveLastRole.into());
         }
+        self.roles.remove(role);
+        Ok(())
     }
 
-    pub fn validate_onramper(&self) -> Result<()> {
-        match self.user_type {
-            UserType::Onramper => Ok(()),
-            UserType::Offramper => Err(UserError::UserNotOnramper.into()),
+    /// Verifies the credentials for `login`, which must be one of the user's linked logins.
+    pub async fn verify_user_auth(
+        &self,
+        login: &LoginAddress,
+        auth_data: Option<AuthenticationData>,
+    ) -> Result<()> {
+        if !self.has_login(login) {
+            return Err(UserError::LoginNotLinked.into());
         }
+        self.verify_login(login, auth_data).await
+    }
+
+    /// Links an already verified `login`. Email logins come with their own password.
+    pub fn link_login(
+        &mut self,
+        login: LoginAddress,
+        hashed_password: Option<String>,
+    ) -> Result<()> {
+        if self.has_login(&login) {
+            return Err(UserError::LoginAlreadyLinked.into());
+        }
+        if self.logins.len() >= Self::MAX_LOGINS {
+            return Err(UserError::TooManyLogins(Self::MAX_LOGINS).into());
+        }
+
+        if let LoginAddress::Email { .. } = login {
+            self.passwords.insert(
+                login.index_key(),
+                hashed_password.ok_or(UserError::PasswordRequired)?,
+            );
+        } else {
+            self.add_address(login.to_transaction_address()?, "login".to_string(), false)?;
+        }
+
+        self.logins.insert(login);
+        Ok(())
+    }
+
+    pub fn unlink_login(&mut self, login: &LoginAddress) -> Result<()> {
+        if !self.has_login(login) {
+            return Err(UserError::LoginNotLinked.into());
+        }
+        if self.logins.len() == 1 {
+            return Err(UserError::CannotRemoveLastLogin.into());
+        }
+
+        let key = login.index_key();
+        self.logins.retain(|login| login.index_key() != key);
+        self.passwords.remove(&key);
+        Ok(())
     }
 
-    pub fn verify_user_auth(&self, auth_data: Option<AuthenticationData>) -> Result<()> {
-        match &self.login {
+    pub fn has_login(&self, login: &LoginAddress) -> bool {
+        let key = login.index_key();
+        self.logins.iter().any(|login| login.index_key() == key)
+    }
+
+    /// Verifies the proof for `login`, whether or not it is linked yet.
+    pub async fn verify_login(
+        &self,
+        login: &LoginAddress,
+        auth_data: Option<AuthenticationData>,
+    ) -> Result<()> {
+        match login {
             LoginAddress::Email { .. } => {
                 let password = auth_data
                     .ok_or(UserError::PasswordRequired)?
                     .password
                     .ok_or(UserError::PasswordRequired)?;
-                let hashed_password =
-                    self.hashed_password
-                        .clone()
-                        .ok_or(SystemError::InternalError(
-                            "Password not in user".to_string(),
-                        ))?;
+                let hashed_password = self.passwords.get(&login.index_key()).cloned().ok_or(
+                    SystemError::InternalError("Password not in user".to_string()),
+                )?;
                 match random::verify_password(&password, &hashed_password) {
                     Ok(true) => {
                         return Ok(());
@@ -107,23 +210,32 @@ impl User {
                 }
             }
             LoginAddress::EVM { address } => {
-                let signature = auth_data
-                    .clone()
-                    .ok_or(UserError::SignatureRequired)?
-                    .signature
-                    .ok_or(UserError::SignatureRequired)?;
-                let message = self.evm_auth_message.as_ref().ok_or_else(|| {
-                    SystemError::InternalError("evm auth message not in user".to_string())
-                })?;
+                let auth_data = auth_data.ok_or(UserError::SignatureRequired)?;
+                let signature = auth_data.signature.ok_or(UserError::SignatureRequired)?;
+                let message = self
+                    .evm_auth_message
+                    .as_ref()
+                    .ok_or(UserError::InvalidAuthMessage)?;
+                let siwe_config = memory::heap::read_state(|s| s.siwe.clone());
+                let siwe_message = SiweMessage::parse(message)?;
+                siwe_message.validate(&siwe_config, address, ic_cdk::api::time())?;
 
-                signer::verify_signature(address, message, &signature)?
+                let digest = match auth_data.signature_type {
+                    None | Some(EvmSignatureType::PersonalSign) => hash_message(message).0,
+                    Some(EvmSignatureType::TypedData) => siwe_message.eip712_hash()?,
+                };
+                signer::verify_signature(
+                    siwe_message.chain_id,
+                    address,
+                    digest,
+                    &signature,
+                    auth_data.contract_wallet.unwrap_or(false),
+                )
+                .await?
             }
             LoginAddress::ICP { principal_id } => {
-                ic_cdk::println!(
-                    "[verify_user_auth] caller = {:?}",
-                    ic_cdk::caller().to_string()
-                );
-                ic_cdk::println!("[verify_user_auth] principal_id = {:?}", principal_id);
+                ic_cdk::println!("[verify_login] caller = {:?}", ic_cdk::caller().to_string());
+                ic_cdk::println!("[verify_login] principal_id = {:?}", principal_id);
                 if ic_cdk::caller()
                     != Principal::from_text(principal_id)
                         .map_err(|_| BlockchainError::InvalidAddress)?
@@ -131,46 +243,356 @@ impl User {
                     return Err(UserError::UnauthorizedPrincipal.into());
                 }
             }
-            _ => return Err(UserError::UnauthorizedPrincipal.into()),
+            LoginAddress::Solana { address } => {
+                let signature = auth_data
+                    .ok_or(UserError::SignatureRequired)?
+                    .signature
+                    .ok_or(UserError::SignatureRequired)?;
+                let message = self
+                    .evm_auth_message
+                    .as_ref()
+                    .ok_or(UserError::InvalidAuthMessage)?;
+                let siwe_config = memory::heap::read_state(|s| s.siwe.clone());
+                WalletAuthMessage::parse(message)?.validate(
+                    &siwe_config,
+                    "Solana",
+                    address,
+                    ic_cdk::api::time(),
+                )?;
+
+                solana::verify_signature(address, message, &signature)?
+            }
+            LoginAddress::Bitcoin { address } => {
+                let signature = auth_data
+                    .ok_or(UserError::SignatureRequired)?
+                    .signature
+                    .ok_or(UserError::SignatureRequired)?;
+                let message = self
+                    .evm_auth_message
+                    .as_ref()
+                    .ok_or(UserError::InvalidAuthMessage)?;
+                let siwe_config = memory::heap::read_state(|s| s.siwe.clone());
+                let network = btc::get_network()?;
+                WalletAuthMessage::parse(message)?.validate(
+                    &siwe_config,
+                    "Bitcoin",
+                    address,
+                    ic_cdk::api::time(),
+                )?;
+
+                btc::verify_signature(&btc::parse_address(address, network)?, message, &signature)?
+            }
         }
 
         Ok(())
     }
 
+    /// Internet Identity users are recognised by their principal and do not need a session token.
     pub fn validate_session(&self, token: &str) -> Result<()> {
-        self.session
-            .as_ref()
-            .ok_or(UserError::SessionNotFound)?
-            .validate(token)
+        if self.is_login_principal(&ic_cdk::caller()) {
+            return Ok(());
+        }
+
+        memory::stable::sessions::validate_session(self.id, token)
+    }
+
+    pub fn is_login_principal(&self, principal: &Principal) -> bool {
+        *principal != Principal::anonymous()
+            && self.logins.iter().any(|login| match login {
+                LoginAddress::ICP { principal_id } => principal.to_text() == *principal_id,
+                _ => false,
+            })
+    }
+
+    /// Whether `provider` matches one of the user's accounts exactly.
+    pub fn has_payment_account(&self, provider: &PaymentProvider) -> bool {
+        self.payment_providers
+            .iter()
+            .any(|account| account.provider == *provider)
+    }
+
+    /// Adds the account or relabels it if already saved, returning its id.
+    pub fn add_payment_account(
+        &mut self,
+        provider: PaymentProvider,
+        label: String,
+    ) -> Result<String> {
+        validate_label(&label)?;
+        let account = PaymentAccount::new(provider, label);
+        let id = account.id.clone();
+        let full = self.payment_providers.len() >= Self::MAX_PAYMENT_ACCOUNTS;
+        match self
+            .payment_providers
+            .iter_mut()
+            .find(|saved| saved.id == account.id)
+        {
+            Some(saved) => *saved = account,
+            None if full => {
+                return Err(UserError::TooManyPaymentAccounts(Self::MAX_PAYMENT_ACCOUNTS).into())
+            }
+            None => self.payment_providers.push(account),
+        }
+        Ok(id)
+    }
+
+    pub fn remove_payment_account(&mut self, account_id: &str) -> Result<()> {
+        let index = self
+            .payment_providers
+            .iter()
+            .position(|account| account.id == account_id)
+            .ok_or(UserError::PaymentAccountNotFound)?;
+        self.payment_providers.remove(index);
+        Ok(())
+    }
+
+    pub fn has_address(&self, address: &TransactionAddress) -> bool {
+        self.addresses.iter().any(|saved| saved.address == *address)
+    }
+
+    pub fn default_address(&self, address_type: &AddressType) -> Option<&TransactionAddress> {
+        self.addresses
+            .iter()
+            .find(|saved| saved.is_default && saved.address.address_type == *address_type)
+            .map(|saved| &saved.address)
+    }
+
+    /// Returns `address` if it is one of the user's addresses, or the default address of
+    /// `address_type` when none is given.
+    pub fn resolve_address(
+        &self,
+        address: Option<TransactionAddress>,
+        address_type: &AddressType,
+    ) -> Result<TransactionAddress> {
+        match address {
+            Some(address) if self.has_address(&address) => Ok(address),
+            Some(_) => Err(UserError::AddressNotInUser.into()),
+            None => self
+                .default_address(address_type)
+                .cloned()
+                .ok_or_else(|| UserError::AddressNotInUser.into()),
+        }
+    }
+
+    /// Adds the address or relabels it if already saved. The first address of a type
+    /// becomes its default.
+    pub fn add_address(
+        &mut self,
+        address: TransactionAddress,
+        label: String,
+        make_default: bool,
+    ) -> Result<()> {
+        validate_label(&label)?;
+        if !self.has_address(&address) && self.addresses.len() >= Self::MAX_ADDRESSES {
+            return Err(UserError::TooManyAddresses(Self::MAX_ADDRESSES).into());
+        }
+        let make_default = make_default || self.default_address(&address.address_type).is_none();
+
+        match self
+            .addresses
+            .iter_mut()
+            .find(|saved| saved.address == address)
+        {
+            Some(saved) => saved.label = label,
+            None => self.addresses.push(UserAddress {
+                label,
+                address: address.clone(),
+                is_default: false,
+            }),
+        }
+
+        if make_default {
+            self.mark_default(&address);
+        }
+        Ok(())
+    }
+
+    pub fn set_default_address(&mut self, address: &TransactionAddress) -> Result<()> {
+        if !self.has_address(address) {
+            return Err(UserError::AddressNotInUser.into());
+        }
+        self.mark_default(address);
+        Ok(())
+    }
+
+    /// Removes the address. If it was the default, the next address of its type takes over.
+    pub fn remove_address(&mut self, address: &TransactionAddress) -> Result<()> {
+        let index = self
+            .addresses
+            .iter()
+            .position(|saved| saved.address == *address)
+            .ok_or(UserError::AddressNotInUser)?;
+
+        let removed = self.addresses.remove(index);
+        if removed.is_default {
+            if let Some(next) = self
+                .addresses
+                .iter_mut()
+                .find(|saved| saved.address.address_type == address.address_type)
+            {
+                next.is_default = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn mark_default(&mut self, address: &TransactionAddress) {
+        for saved in self
+            .addresses
+            .iter_mut()
+            .filter(|saved| saved.address.address_type == address.address_type)
+        {
+            saved.is_default = saved.address == *address;
+        }
     }
 
     pub fn update_fiat_amount(&mut self, amount: u64, currency: &str) {
         *self.fiat_amounts.entry(currency.to_string()).or_insert(0) += amount;
     }
 
-    pub fn decrease_score(&mut self) {
-        self.score -= 1;
+    /// Checks that trading `amount` cents of `currency` keeps the user within the limits of
+    /// their verification level.
+    pub fn check_trading_limit(&self, currency: &str, amount: u64) -> Result<()> {
+        let limit = self.trading_limit(currency)?;
+        memory::stable::volumes::get_volume(self.id).check(
+            currency,
+            amount,
+            &limit,
+            ic_cdk::api::time(),
+        )
+    }
+
+    /// Like `check_trading_limit`, but also reserves `amount` for `order_id` until the order is
+    /// paid or unlocked.
+    pub fn reserve_trading_volume(&self, order_id: u64, currency: &str, amount: u64) -> Result<()> {
+        let limit = self.trading_limit(currency)?;
+        memory::stable::volumes::reserve_volume(self.id, order_id, currency, amount, &limit)
+    }
+
+    fn trading_limit(&self, currency: &str) -> Result<FiatLimit> {
+        memory::heap::read_state(|s| s.verification.get_limit(&self.verification_level, currency))
+    }
+
+    pub fn score(&self, role: &UserType) -> i32 {
+        self.scores.get(role).copied().unwrap_or_default()
+    }
+
+    pub fn decrease_score(&mut self, role: &UserType) {
+        *self.scores.entry(role.clone()).or_default() -= 1;
     }
 
-    pub fn increase_score(&mut self) {
-        self.score += 1;
+    pub fn increase_score(&mut self, role: &UserType) {
+        *self.scores.entry(role.clone()).or_default() += 1;
     }
 
-    pub fn is_banned(&self) -> Result<()> {
-        if self.score < 0 {
+    pub fn is_banned(&self, role: &UserType) -> Result<()> {
+        self.moderation.check_active(ic_cdk::api::time())?;
+        if self.score(role) < 0 {
             return Err(UserError::UserBanned.into());
         }
         Ok(())
     }
 }
 
+/// Earlier layouts of the stored user record. Fields that changed shape are optional so that
+/// any past version decodes into this struct.
+#[derive(CandidType, Deserialize)]
+struct LegacyUser {
+    id: u64,
+    user_type: Option<UserType>,
+    roles: Option<HashSet<UserType>>,
+    payment_providers: HashSet<PaymentProvider>,
+    addresses: HashSet<TransactionAddress>,
+    fiat_amounts: HashMap<String, u64>,
+    score: Option<i32>,
+    scores: Option<HashMap<UserType, i32>>,
+    login: Option<LoginAddress>,
+    logins: Option<HashSet<LoginAddress>>,
+    hashed_password: Option<String>,
+    passwords: Option<HashMap<String, String>>,
+    evm_auth_message: Option<String>,
+    verification_level: Option<VerificationLevel>,
+    moderation: Option<ModerationStatus>,
+}
+
+impl From<LegacyUser> for User {
+    fn from(legacy: LegacyUser) -> Self {
+        let roles = legacy
+            .roles
+            .or_else(|| legacy.user_type.clone().map(|role| HashSet::from([role])))
+            .unwrap_or_default();
+        let scores = legacy.scores.unwrap_or_else(|| {
+            roles
+                .iter()
+                .map(|role| (role.clone(), legacy.score.unwrap_or(1)))
+                .collect()
+        });
+
+        let logins = legacy
+            .logins
+            .or_else(|| legacy.login.map(|login| HashSet::from([login])))
+            .unwrap_or_default();
+        // a single password was shared by the email logins
+        let passwords = legacy.passwords.unwrap_or_else(|| {
+            logins
+                .iter()
+                .filter(|login| matches!(login, LoginAddress::Email { .. }))
+                .filter_map(|login| {
+                    legacy
+                        .hashed_password
+                        .clone()
+                        .map(|hash| (login.index_key(), hash))
+                })
+                .collect()
+        });
+
+        User {
+            id: legacy.id,
+            roles,
+            payment_providers: legacy
+                .payment_providers
+                .into_iter()
+                .map(|provider| PaymentAccount::new(provider, "default".to_string()))
+                .collect(),
+            addresses: legacy
+                .addresses
+                .into_iter()
+                .map(|address| UserAddress {
+                    label: "default".to_string(),
+                    address,
+                    is_default: true, // one address per type was allowed
+                })
+                .collect(),
+            fiat_amounts: legacy.fiat_amounts,
+            scores,
+            logins,
+            passwords,
+            evm_auth_message: legacy.evm_auth_message,
+            verification_level: legacy.verification_level.unwrap_or_default(),
+            moderation: legacy.moderation.unwrap_or_default(),
+        }
+    }
+}
+
+fn validate_label(label: &str) -> Result<()> {
+    if label.len() > User::MAX_LABEL_LENGTH {
+        return Err(SystemError::InvalidInput(format!(
+            "Label is longer than {} bytes",
+            User::MAX_LABEL_LENGTH
+        ))
+        .into());
+    }
+    Ok(())
+}
+
 impl Storable for User {
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
         Cow::Owned(Encode!(self).unwrap())
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        Decode!(bytes.as_ref(), Self)
+            .or_else(|_| Decode!(bytes.as_ref(), LegacyUser).map(User::from))
+            .unwrap()
     }
 
     const BOUND: Bound = Bound::Bounded {
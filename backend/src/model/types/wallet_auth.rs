@@ -0,0 +1,127 @@
+use crate::errors::{Result, UserError};
+
+use super::evm::siwe::{expect_line, field, format_timestamp, parse_timestamp, SiweConfig};
+
+const PREAMBLE_INFIX: &str = " wants you to sign in with your ";
+const PREAMBLE_SUFFIX: &str = " account:";
+
+/// Sign-in challenge for non-EVM wallets (Solana, Bitcoin), following the same layout as EIP-4361.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WalletAuthMessage {
+    pub domain: String,
+    pub account: String, // e.g. "Solana"
+    pub address: String,
+    pub statement: Option<String>,
+    pub uri: String,
+    pub nonce: String,
+    pub issued_at: u64,       // nanoseconds
+    pub expiration_time: u64, // nanoseconds
+}
+
+impl WalletAuthMessage {
+    pub fn new(config: &SiweConfig, account: &str, address: &str, nonce: String) -> Self {
+        let issued_at = ic_cdk::api::time();
+
+        WalletAuthMessage {
+            domain: config.domain.clone(),
+            account: account.to_string(),
+            address: address.to_string(),
+            statement: config.statement.clone(),
+            uri: config.uri.clone(),
+            nonce,
+            issued_at,
+            expiration_time: issued_at + config.expiration_secs * 1_000_000_000,
+        }
+    }
+
+    pub fn validate(
+        &self,
+        config: &SiweConfig,
+        account: &str,
+        address: &str,
+        now: u64,
+    ) -> Result<()> {
+        if self.domain != config.domain
+            || self.uri != config.uri
+            || self.account != account
+            || self.address != address
+        {
+            return Err(UserError::InvalidAuthMessage.into());
+        }
+        if now < self.issued_at {
+            return Err(UserError::InvalidAuthMessage.into());
+        }
+        if now >= self.expiration_time {
+            return Err(UserError::AuthMessageExpired.into());
+        }
+        Ok(())
+    }
+
+    pub fn parse(message: &str) -> Result<Self> {
+        let mut lines = message.lines();
+
+        let (domain, account) = lines
+            .next()
+            .and_then(|line| line.strip_suffix(PREAMBLE_SUFFIX))
+            .and_then(|line| line.split_once(PREAMBLE_INFIX))
+            .filter(|(domain, account)| !domain.is_empty() && !account.is_empty())
+            .ok_or(UserError::InvalidAuthMessage)?;
+        let address = lines
+            .next()
+            .filter(|address| !address.is_empty())
+            .ok_or(UserError::InvalidAuthMessage)?
+            .to_string();
+        expect_line(lines.next(), "")?;
+
+        let mut statement = None;
+        let mut next = lines.next();
+        if let Some(line) = next.filter(|line| !line.starts_with("URI: ")) {
+            statement = Some(line.to_string());
+            expect_line(lines.next(), "")?;
+            next = lines.next();
+        }
+
+        let uri = field(next, "URI")?;
+        let nonce = field(lines.next(), "Nonce")?;
+        let issued_at = parse_timestamp(&field(lines.next(), "Issued At")?)?;
+        let expiration_time = parse_timestamp(&field(lines.next(), "Expiration Time")?)?;
+        if lines.next().is_some() {
+            return Err(UserError::InvalidAuthMessage.into());
+        }
+
+        Ok(WalletAuthMessage {
+            domain: domain.to_string(),
+            account: account.to_string(),
+            address,
+            statement,
+            uri,
+            nonce,
+            issued_at,
+            expiration_time,
+        })
+    }
+}
+
+impl std::fmt::Display for WalletAuthMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{}{}{}{}",
+            self.domain, PREAMBLE_INFIX, self.account, PREAMBLE_SUFFIX
+        )?;
+        writeln!(f, "{}", self.address)?;
+        writeln!(f)?;
+        if let Some(statement) = &self.statement {
+            writeln!(f, "{}", statement)?;
+            writeln!(f)?;
+        }
+        writeln!(f, "URI: {}", self.uri)?;
+        writeln!(f, "Nonce: {}", self.nonce)?;
+        writeln!(f, "Issued At: {}", format_timestamp(self.issued_at))?;
+        write!(
+            f,
+            "Expiration Time: {}",
+            format_timestamp(self.expiration_time)
+        )
+    }
+}
@@ -0,0 +1,141 @@
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod,
+};
+use num_traits::ToPrimitive;
+use serde_json::json;
+
+use crate::{
+    errors::{Result, SystemError},
+    model::{
+        memory::{heap::read_state, stable::secrets},
+        types::{mail::MailConfig, secrets::SecretKey},
+    },
+};
+
+/// Fails unless a mail backend is configured, so that tokens are not issued that cannot be delivered.
+pub fn ensure_mail_configured() -> Result<()> {
+    let configured = match read_state(|s| s.mail.clone()) {
+        MailConfig::Disabled => false,
+        MailConfig::Mock => true,
+        MailConfig::Https { .. } => secrets::contains_secret(SecretKey::MailApiKey),
+    };
+    if !configured {
+        return Err(
+            SystemError::InternalError("Mail delivery is not configured".to_string()).into(),
+        );
+    }
+    Ok(())
+}
+
+pub async fn send_password_reset_email(email: &str, token: &str) -> Result<()> {
+    let reset_url = match read_state(|s| s.mail.clone()) {
+        MailConfig::Https { reset_url, .. } => reset_url,
+        _ => String::new(),
+    };
+
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("email", email)
+        .append_pair("token", token)
+        .finish();
+
+    send_email(
+        email,
+        "Reset your password",
+        format!(
+            "Use the following link to reset your password: {}?{}\nThe link expires in 30 minutes.",
+            reset_url, query
+        ),
+        format!("password-reset-{}", token),
+    )
+    .await
+}
+
+pub async fn send_email_verification_email(email: &str, token: &str) -> Result<()> {
+    send_email(
+        email,
+        "Verify your email",
+        format!(
+            "Use the following code to add this email to your icRamp account: {}\nThe code expires in 30 minutes.",
+            token
+        ),
+        format!("email-verification-{}", token),
+    )
+    .await
+}
+
+async fn send_email(
+    email: &str,
+    subject: &str,
+    text: String,
+    idempotency_key: String,
+) -> Result<()> {
+    let (mail, proxy_url) = read_state(|s| (s.mail.clone(), s.proxy_url.clone()));
+
+    let (api_url, sender) = match mail {
+        MailConfig::Disabled => {
+            return Err(SystemError::InternalError(
+                "Mail delivery is not configured".to_string(),
+            ))?
+        }
+        MailConfig::Mock => {
+            ic_cdk::println!("[send_email] mock mail to {}: {}", email, text);
+            return Ok(());
+        }
+        MailConfig::Https {
+            api_url, sender, ..
+        } => (api_url, sender),
+    };
+    let api_key = secrets::get_secret_string(SecretKey::MailApiKey)?;
+
+    let request_headers = vec![
+        HttpHeader {
+            name: "Content-Type".to_string(),
+            value: "application/json".to_string(),
+        },
+        HttpHeader {
+            name: "Authorization".to_string(),
+            value: format!("Bearer {}", api_key),
+        },
+        HttpHeader {
+            name: "x-forwarded-host".to_string(),
+            value: api_url,
+        },
+        HttpHeader {
+            name: "idempotency-key".to_string(),
+            value: idempotency_key,
+        },
+    ];
+
+    let request_body = json!({
+        "from": sender,
+        "to": email,
+        "subject": subject,
+        "text": text,
+    })
+    .to_string()
+    .into_bytes();
+
+    let request = CanisterHttpRequestArgument {
+        url: format!("{}/send", proxy_url),
+        method: HttpMethod::POST,
+        body: Some(request_body),
+        max_response_bytes: Some(2048),
+        transform: None,
+        headers: request_headers,
+    };
+
+    let cycles: u128 = 10_000_000_000;
+    match http_request(request, cycles).await {
+        Ok((response,)) => {
+            let status = response.status.0.to_u64().unwrap_or_default();
+            if status >= 300 {
+                return Err(SystemError::HttpRequestError(
+                    status,
+                    String::from_utf8_lossy(&response.body).to_string(),
+                ))?;
+            }
+            Ok(())
+        }
+        Err((r, m)) => Err(SystemError::HttpRequestError(r as u64, m).into()),
+    }
+}
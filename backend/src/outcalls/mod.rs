@@ -1,3 +1,4 @@
+pub mod mail;
 pub mod paypal;
 pub mod revolut;
 pub mod xrc_rates;
@@ -8,8 +8,8 @@ use ic_cdk::api::time;
 
 use crate::{
     errors::{Result, SystemError},
-    model::memory::heap::read_state,
-    types::payment::paypal,
+    model::memory::{heap::read_state, stable::secrets},
+    types::{payment::paypal, secrets::SecretKey},
 };
 
 #[derive(Serialize, Deserialize)]
@@ -33,14 +33,14 @@ pub async fn get_paypal_access_token() -> Result<String> {
     }
 
     ic_cdk::println!("[get_paypal_access_token] Fetching new token from PayPal");
-    let (client_id, client_secret, api_url, proxy_url) = read_state(|s| {
+    let (client_id, api_url, proxy_url) = read_state(|s| {
         (
             s.paypal.client_id.clone(),
-            s.paypal.client_secret.clone(),
             s.paypal.api_url.clone(),
             s.proxy_url.clone(),
         )
     });
+    let client_secret = secrets::get_secret_string(SecretKey::PaypalClientSecret)?;
     let credentials = general_purpose::STANDARD.encode(format!("{}:{}", client_id, client_secret));
 
     let request_headers = vec![
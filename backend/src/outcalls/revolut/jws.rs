@@ -6,7 +6,8 @@ use sha2::{Digest, Sha256};
 
 use crate::errors::{Result, SystemError};
 use crate::management::random;
-use crate::model::memory::heap::read_state;
+use crate::model::memory::stable::secrets;
+use crate::types::secrets::SecretKey;
 
 #[derive(Serialize)]
 pub struct JWSHeader {
@@ -39,7 +40,7 @@ impl JWSHeader {
 }
 
 pub async fn create_jws_signature(payload: &str, jws_header: &JWSHeader) -> Result<String> {
-    let private_key_der = read_state(|s| s.revolut.private_key_der.clone());
+    let private_key_der = secrets::get_secret(SecretKey::RevolutPrivateKey)?;
 
     // Encode the JWS header and payload
     let jws_header_json =
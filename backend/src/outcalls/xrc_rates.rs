@@ -2,11 +2,16 @@ use candid::{CandidType, Deserialize, Principal};
 use ic_cdk::api::call::call_with_payment128;
 
 use crate::{
-    errors::{Result, SystemError},
-    model::memory::heap,
+    errors::{OrderError, Result, SystemError},
+    model::{
+        memory::heap::{self, read_state},
+        types::exchange_rate::{RateQualityConfig, RejectedRate},
+    },
 };
 
 const XRC_CANISTER_ID: &str = "uf6dk-hyaaa-aaaaq-qaaaq-cai";
+// Every XRC call needs 1B cycles.
+pub(crate) const XRC_CALL_CYCLES: u128 = 1_000_000_000;
 
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
 pub enum AssetClass {
@@ -28,14 +33,14 @@ struct GetExchangeRateRequest {
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
-struct ExchangeRateMetadata {
-    decimals: u32,
-    base_asset_num_received_rates: u64,
-    base_asset_num_queried_sources: u64,
-    quote_asset_num_received_rates: u64,
-    quote_asset_num_queried_sources: u64,
-    standard_deviation: u64,
-    forex_timestamp: Option<u64>,
+pub struct ExchangeRateMetadata {
+    pub decimals: u32,
+    pub base_asset_num_received_rates: u64,
+    pub base_asset_num_queried_sources: u64,
+    pub quote_asset_num_received_rates: u64,
+    pub quote_asset_num_queried_sources: u64,
+    pub standard_deviation: u64,
+    pub forex_timestamp: Option<u64>,
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
@@ -47,6 +52,69 @@ struct ExchangeRate {
     metadata: ExchangeRateMetadata,
 }
 
+impl ExchangeRate {
+    fn to_float(&self) -> f64 {
+        let float_rate = self.rate as f64;
+        let float_divisor = 10u64.pow(self.metadata.decimals) as f64;
+        float_rate / float_divisor
+    }
+
+    /// Returns the reasons why the rate does not meet the configured quality
+    /// thresholds. An empty list means the rate can be used.
+    fn quality_issues(&self, config: &RateQualityConfig) -> Vec<String> {
+        let mut issues = Vec::new();
+        let metadata = &self.metadata;
+
+        // assets that are not queried from any source (e.g. USD) are not taken into account
+        let received_rates = [
+            (
+                metadata.base_asset_num_queried_sources,
+                metadata.base_asset_num_received_rates,
+            ),
+            (
+                metadata.quote_asset_num_queried_sources,
+                metadata.quote_asset_num_received_rates,
+            ),
+        ]
+        .into_iter()
+        .filter(|(queried, _)| *queried > 0)
+        .map(|(_, received)| received)
+        .min();
+        if let Some(received) = received_rates {
+            if received < config.min_sources {
+                issues.push(format!(
+                    "only {} sources received, minimum is {}",
+                    received, config.min_sources
+                ));
+            }
+        }
+
+        if self.rate == 0 {
+            issues.push("rate is zero".to_string());
+        } else {
+            let std_dev_bps = metadata.standard_deviation as u128 * 10_000 / self.rate as u128;
+            if std_dev_bps > config.max_std_dev_bps as u128 {
+                issues.push(format!(
+                    "relative standard deviation of {} bps exceeds {} bps",
+                    std_dev_bps, config.max_std_dev_bps
+                ));
+            }
+        }
+
+        if let Some(forex_timestamp) = metadata.forex_timestamp {
+            let forex_age = self.timestamp.saturating_sub(forex_timestamp);
+            if forex_age > config.max_forex_age_secs {
+                issues.push(format!(
+                    "forex rates are {}s old, maximum is {}s",
+                    forex_age, config.max_forex_age_secs
+                ));
+            }
+        }
+
+        issues
+    }
+}
+
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub enum ExchangeRateError {
     AnonymousPrincipalNotAllowed,
@@ -73,38 +141,59 @@ enum GetExchangeRateResult {
     Err(ExchangeRateError),
 }
 
-async fn get_xrc_exchange_rate(base_asset: Asset, quote_asset: Asset) -> Result<f64> {
+async fn get_xrc_exchange_rate(base_asset: Asset, quote_asset: Asset) -> Result<ExchangeRate> {
     let request = GetExchangeRateRequest {
         base_asset,
         quote_asset,
         timestamp: Some(ic_cdk::api::time() / 1_000_000_000),
     };
 
-    // Every XRC call needs 1B cycles.
-    let cycles: u128 = 1_000_000_000;
     let result: (GetExchangeRateResult,) = call_with_payment128(
         Principal::from_text(XRC_CANISTER_ID).expect(" xrc canister id should be defined "),
         "get_exchange_rate",
         (request,),
-        cycles,
+        XRC_CALL_CYCLES,
     )
     .await
     .map_err(|e| SystemError::CanisterCallError(format!("{:?}", e)))?;
 
     match result.0 {
-        GetExchangeRateResult::Ok(rate_response) => {
-            let float_rate = rate_response.rate as f64;
-            let float_divisor = 10u64.pow(rate_response.metadata.decimals) as f64;
-            Ok(float_rate / float_divisor)
-        }
+        GetExchangeRateResult::Ok(rate_response) => Ok(rate_response),
         GetExchangeRateResult::Err(err) => Err(SystemError::ExchangeRateError(err).into()),
     }
 }
 
-pub async fn get_cached_exchange_rate(
-    mut base_asset: Asset,
-    mut quote_asset: Asset,
-) -> Result<f64> {
+/// Fetches the rate from the XRC canister and rejects it if it does not meet
+/// the quality thresholds defined in the state.
+async fn get_validated_xrc_rate(base_asset: Asset, quote_asset: Asset) -> Result<f64> {
+    let exchange_rate = get_xrc_exchange_rate(base_asset.clone(), quote_asset.clone()).await?;
+
+    let config = read_state(|s| s.rate_quality.clone());
+    let issues = exchange_rate.quality_issues(&config);
+    if !issues.is_empty() {
+        ic_cdk::println!(
+            "[get_validated_xrc_rate] rejected {}/{} rate: {:?}, metadata = {:?}",
+            base_asset.symbol,
+            quote_asset.symbol,
+            issues,
+            exchange_rate.metadata
+        );
+        heap::register_rejected_rate(RejectedRate {
+            base_symbol: base_asset.symbol,
+            quote_symbol: quote_asset.symbol,
+            rate: exchange_rate.to_float(),
+            metadata: exchange_rate.metadata,
+            reasons: issues.clone(),
+            rejected_at: ic_cdk::api::time(),
+        });
+        return Err(SystemError::ExchangeRateRejected(issues.join("; ")).into());
+    }
+
+    heap::clear_rejected_rate(&base_asset.symbol, &quote_asset.symbol);
+    Ok(exchange_rate.to_float())
+}
+
+fn normalize_assets(mut base_asset: Asset, mut quote_asset: Asset) -> (Asset, Asset) {
     if base_asset.class == AssetClass::Cryptocurrency
         && (base_asset.symbol == "USD" || base_asset.symbol == "EUR")
     {
@@ -116,27 +205,127 @@ pub async fn get_cached_exchange_rate(
         quote_asset.class = AssetClass::FiatCurrency;
     }
 
+    (base_asset, quote_asset)
+}
+
+pub async fn get_cached_exchange_rate(base_asset: Asset, quote_asset: Asset) -> Result<f64> {
+    let (base_asset, quote_asset) = normalize_assets(base_asset, quote_asset);
+
+    match get_stablecoin_legs(&base_asset, &quote_asset) {
+        Some((peg_leg, forex_leg)) => {
+            let peg_rate = get_cached_pair_rate(peg_leg.0, peg_leg.1).await?;
+            let forex_rate = get_cached_pair_rate(forex_leg.0, forex_leg.1).await?;
+            Ok(peg_rate * forex_rate)
+        }
+        None => get_cached_pair_rate(base_asset, quote_asset).await,
+    }
+}
+
+async fn get_cached_pair_rate(base_asset: Asset, quote_asset: Asset) -> Result<f64> {
     if let Some(predefined_rate) =
         get_predefined_rate_if_stablecoin(&base_asset.symbol, &quote_asset.symbol)
     {
-        Ok(predefined_rate)
-    } else {
-        match heap::get_cached_rate(base_asset.clone(), quote_asset.clone()) {
-            Some(cache) => Ok(cache),
-            None => {
-                ic_cdk::println!("[get_cached_exchange_rate] Recalculating cache.");
-                let rate = get_xrc_exchange_rate(base_asset.clone(), quote_asset.clone()).await?;
-                heap::cache_exchange_rate(base_asset, quote_asset, rate);
-                Ok(rate)
-            }
+        return Ok(predefined_rate);
+    }
+
+    match heap::get_cached_rate(base_asset.clone(), quote_asset.clone()) {
+        Some(cache) => Ok(cache),
+        None => {
+            ic_cdk::println!("[get_cached_exchange_rate] Recalculating cache.");
+            let rate = get_validated_xrc_rate(base_asset.clone(), quote_asset.clone()).await?;
+            heap::cache_exchange_rate(base_asset, quote_asset, rate);
+            Ok(rate)
         }
     }
 }
 
+/// Splits a stablecoin/fiat pair into the stablecoin/peg leg, priced with the XRC
+/// stablecoin rates, and the peg/fiat forex leg.
+fn get_stablecoin_legs(
+    base_asset: &Asset,
+    quote_asset: &Asset,
+) -> Option<((Asset, Asset), (Asset, Asset))> {
+    if base_asset.class != AssetClass::Cryptocurrency
+        || quote_asset.class != AssetClass::FiatCurrency
+    {
+        return None;
+    }
+    let peg = read_state(|s| s.stablecoins.get_peg(&base_asset.symbol))?;
+    let peg_asset = Asset {
+        class: AssetClass::FiatCurrency,
+        symbol: peg,
+    };
+
+    Some((
+        (base_asset.clone(), peg_asset.clone()),
+        (peg_asset, quote_asset.clone()),
+    ))
+}
+
+/// Fails if `symbol` is a registered stablecoin trading away from its peg by more
+/// than the configured threshold.
+pub async fn check_stablecoin_peg(symbol: &str) -> Result<()> {
+    let Some(peg) = read_state(|s| s.stablecoins.get_peg(symbol)) else {
+        return Ok(());
+    };
+
+    let rate = get_cached_pair_rate(
+        Asset {
+            class: AssetClass::Cryptocurrency,
+            symbol: symbol.to_string(),
+        },
+        Asset {
+            class: AssetClass::FiatCurrency,
+            symbol: peg.clone(),
+        },
+    )
+    .await?;
+
+    if read_state(|s| s.stablecoins.is_depegged(rate)) {
+        return Err(OrderError::StablecoinDepegged {
+            symbol: symbol.to_string(),
+            peg,
+            rate,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Returns the pairs that have to be fetched from the XRC canister so that the
+/// rate for `base_asset`/`quote_asset` stays cached for at least `margin` more nanoseconds.
+pub fn get_expiring_pairs(
+    base_asset: Asset,
+    quote_asset: Asset,
+    margin: u64,
+) -> Vec<(Asset, Asset)> {
+    let (base_asset, quote_asset) = normalize_assets(base_asset, quote_asset);
+
+    let pairs = match get_stablecoin_legs(&base_asset, &quote_asset) {
+        Some((peg_leg, forex_leg)) => vec![peg_leg, forex_leg],
+        None => vec![(base_asset, quote_asset)],
+    };
+
+    pairs
+        .into_iter()
+        .filter(|(base, quote)| {
+            get_predefined_rate_if_stablecoin(&base.symbol, &quote.symbol).is_none()
+                && heap::rate_expires_within(base, quote, margin)
+        })
+        .collect()
+}
+
+/// Fetches a fresh rate from the XRC canister and caches it, regardless of the
+/// current state of the cache.
+pub async fn refresh_exchange_rate(base_asset: Asset, quote_asset: Asset) -> Result<f64> {
+    let rate = get_validated_xrc_rate(base_asset.clone(), quote_asset.clone()).await?;
+    heap::cache_exchange_rate(base_asset, quote_asset, rate);
+    Ok(rate)
+}
+
 fn get_predefined_rate_if_stablecoin(base_symbol: &str, quote_symbol: &str) -> Option<f64> {
-    match (base_symbol, quote_symbol) {
-        ("USD", "USD") => Some(1.0),
-        ("EUR", "EUR") => Some(1.0),
-        _ => None,
+    if base_symbol == quote_symbol {
+        return Some(1.0);
     }
+    None
 }
@@ -159,6 +159,8 @@ dfx deploy backend --argument "(
         tan = \"test-jwk.s3.eu-west-3.amazonaws.com\";
       };
       proxy_url = \"https://ic2p2ramp.xyz\";
+      bitcoin_network = variant { regtest };
+      mail = opt variant { Mock };
     }
   }
 )"
@@ -67,6 +67,7 @@ dfx deploy backend_prod --argument "(
         tan = \"test-jwk.s3.eu-west-3.amazonaws.com\";
       };
       proxy_url = \"https://ic2p2ramp.xyz\";
+      bitcoin_network = variant { mainnet };
     }
   }
 )" --ic